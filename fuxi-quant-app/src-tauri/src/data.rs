@@ -0,0 +1,75 @@
+use fuxi_quant_core::backtest::history::{self, SyncProgress};
+use fuxi_quant_core::helpers::time_from_str;
+use serde::Serialize;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tauri::ipc::Channel;
+
+/// 进度推送最小间隔
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(100);
+
+/// 数据同步事件
+#[derive(Clone, Debug, Serialize)]
+#[serde(tag = "type", content = "data")]
+pub enum SyncEvent {
+    Progress(SyncProgress),
+    Done,
+    Error(String),
+}
+
+/// 同步回测数据（进度通过 Channel 推送）
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+pub async fn sync_data(
+    data_dir: String,
+    codes: Vec<String>,
+    market: String,
+    start_time: String,
+    end_time: String,
+    interval: String,
+    history_bar_len: usize,
+    concurrency: usize,
+    channel: Channel<SyncEvent>,
+) -> Result<(), String> {
+    tauri::async_runtime::spawn_blocking(move || {
+        let start_time = time_from_str(&start_time).map_err(|e| e.to_string())?;
+        let end_time = time_from_str(&end_time).map_err(|e| e.to_string())?;
+
+        // 下载进度按字节回调, 节流后推送; 文件完成时总是推送
+        let progress_channel = channel.clone();
+        let last_sent = Mutex::new(None::<(Instant, usize)>);
+        let result = history::sync_bars(
+            &data_dir,
+            &codes,
+            &market,
+            start_time,
+            end_time,
+            &interval,
+            history_bar_len,
+            concurrency,
+            move |progress| {
+                let mut last_sent = last_sent.lock().unwrap();
+                let due = last_sent.is_none_or(|(time, files_done)| {
+                    time.elapsed() >= PROGRESS_INTERVAL || files_done != progress.files_done
+                });
+                if due {
+                    *last_sent = Some((Instant::now(), progress.files_done));
+                    let _ = progress_channel.send(SyncEvent::Progress(progress.clone()));
+                }
+            },
+        );
+
+        match result {
+            Ok(()) => {
+                let _ = channel.send(SyncEvent::Done);
+                Ok(())
+            }
+            Err(e) => {
+                let _ = channel.send(SyncEvent::Error(format!("{e:#}")));
+                Err(format!("{e:#}"))
+            }
+        }
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
@@ -1,4 +1,5 @@
 mod agent;
+mod data;
 
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
@@ -7,7 +8,8 @@ pub fn run() {
         .invoke_handler(tauri::generate_handler![
             agent::init_agent,
             agent::chat,
-            agent::clear_chat
+            agent::clear_chat,
+            data::sync_data
         ])
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
@@ -1,8 +1,58 @@
-use crate::{helpers::*, types::*};
-use anyhow::{Result, anyhow, ensure};
+use crate::{fee::FeeSchedule, helpers::*, types::*};
+use anyhow::{Result, anyhow, bail, ensure};
 use chrono::{Duration, DurationRound, Timelike};
-use polars::frame::DataFrame;
+use polars::prelude::{DataFrame, DataType, IdxSize, IntoLazy, col, lit};
+use rand::{Rng, SeedableRng, rngs::StdRng};
 use rust_decimal::prelude::*;
+use std::collections::VecDeque;
+
+/// 延迟模型: 下单/撤单经过延迟(含随机抖动)后生效
+pub struct Latency {
+    /// 下单延迟
+    pub submit: Duration,
+    /// 撤单延迟
+    pub cancel: Duration,
+    /// 随机抖动上限
+    pub jitter: Duration,
+    /// 随机数生成器
+    rng: StdRng,
+}
+
+impl Latency {
+    pub fn new(submit_ms: u64, cancel_ms: u64, jitter_ms: u64, seed: u64) -> Self {
+        Self {
+            submit: Duration::milliseconds(submit_ms as i64),
+            cancel: Duration::milliseconds(cancel_ms as i64),
+            jitter: Duration::milliseconds(jitter_ms as i64),
+            rng: StdRng::seed_from_u64(seed),
+        }
+    }
+
+    /// 随机抖动
+    fn jitter(&mut self) -> Duration {
+        let max_ms = self.jitter.num_milliseconds();
+        if max_ms <= 0 {
+            return Duration::zero();
+        }
+        Duration::milliseconds(self.rng.random_range(0..=max_ms))
+    }
+
+    /// 下单延迟
+    pub fn submit_delay(&mut self) -> Duration {
+        self.submit + self.jitter()
+    }
+
+    /// 撤单延迟
+    pub fn cancel_delay(&mut self) -> Duration {
+        self.cancel + self.jitter()
+    }
+}
+
+impl Default for Latency {
+    fn default() -> Self {
+        Self::new(0, 0, 0, 0)
+    }
+}
 
 /// 回测引擎
 pub struct Backtest {
@@ -12,12 +62,26 @@ pub struct Backtest {
     pub strategy: Box<dyn Strategy>,
     /// 初始资金
     pub init_cash: Decimal,
-    /// 挂单手续费率
-    pub maker_fee_rate: Decimal,
-    /// 吃单手续费率
-    pub taker_fee_rate: Decimal,
-    /// 滑点
-    pub slippage: Decimal,
+    /// 手续费方案
+    pub fee_schedule: FeeSchedule,
+    /// 滑点模型
+    pub slippage: Box<dyn SlippageModel>,
+    /// 成交量参与率上限(单根K线成交数量占K线成交量的比例, 0表示不限制)
+    pub participation_rate: Decimal,
+    /// 强平手续费率
+    pub liquidation_fee_rate: Decimal,
+    /// K线内价格路径模型
+    pub intrabar_path: IntrabarPath,
+    /// 延迟模型
+    pub latency: Latency,
+    /// 秒级K线(用于细化K线内价格路径)
+    pub sub_bars: Map<String, SubBars>,
+    /// 当前K线价格(开高低收, 按最小价格变动取整)
+    pub bar_prices: Map<String, [Decimal; 4]>,
+    /// 当前K线成交量
+    pub bar_sizes: Map<String, Decimal>,
+    /// K线周期
+    pub interval: Duration,
     /// 历史K线长度
     pub history_bar_len: usize,
     /// K线索引
@@ -32,6 +96,10 @@ pub struct Backtest {
     pub history_equities: Vec<f64>,
     /// 交易记录
     pub trades: Vec<Trade>,
+    /// 资金费率历史(未结算部分)
+    pub funding_rates: Map<String, VecDeque<(Time, Decimal)>>,
+    /// 资金费记录
+    pub fundings: Vec<Funding>,
 }
 
 impl Backtest {
@@ -40,200 +108,368 @@ impl Backtest {
         codes: &[String],
         start_time: Time,
         end_time: Time,
+        interval: &str,
         cash: Decimal,
         history_bar_len: usize,
-        maker_fee_rate: Decimal,
-        taker_fee_rate: Decimal,
-        slippage: Decimal,
-        data_dir: &str,
+        fee_schedule: FeeSchedule,
+        slippage: Box<dyn SlippageModel>,
+        participation_rate: Decimal,
+        liquidation_fee_rate: Decimal,
+        intrabar_path: &str,
+        sub_bar: bool,
+        latency: Latency,
+        position_mode: &str,
+        history_len: usize,
+        data_source: &dyn DataSource,
     ) -> Result<Self> {
-        let start_time = start_time.duration_trunc(Duration::minutes(1))?;
-        let end_time = end_time.duration_trunc(Duration::minutes(1))?;
+        let interval_dur = interval_from_str(interval)?;
+        let intrabar_path = IntrabarPath::from_str(intrabar_path)?;
+        let position_mode = PositionMode::from_str(position_mode)?;
+        let start_time = start_time.duration_trunc(interval_dur)?;
+        let end_time = end_time.duration_trunc(interval_dur)?;
 
         ensure!(end_time >= start_time);
         ensure!(!codes.is_empty());
         ensure!(!cash.is_sign_negative());
-        ensure!(!slippage.is_sign_negative());
+        ensure!(!participation_rate.is_sign_negative() && participation_rate <= Decimal::ONE);
+        ensure!(!liquidation_fee_rate.is_sign_negative());
 
         let mut context = Context::new(cash, codes)?;
+        context.position_mode = position_mode;
+        context.history_len = history_len;
+
+        for code in codes {
+            let symbol = data_source
+                .symbol(code)?
+                .ok_or_else(|| anyhow!("交易对元数据缺失: 交易对={code}, 数据源未提供"))?;
+            context.symbols.insert(code.clone(), symbol);
+        }
+        // 账户资金为单一币种, U本位与币本位合约不能混合回测
+        ensure!(
+            context.symbols.values().all(|symbol| !symbol.inverse)
+                || (codes.len() == 1 && context.symbols.values().all(|symbol| symbol.inverse)),
+            "U本位与币本位合约不能混合回测, 币本位合约仅支持单交易对"
+        );
 
         let mut bars = Map::with_capacity(codes.len());
         for code in codes {
             bars.insert(
                 code.clone(),
-                bars::load(data_dir, code, start_time, end_time, history_bar_len)?,
+                data_source.load(code, start_time, end_time, interval, history_bar_len)?,
             );
         }
         context.bars = bars;
 
+        // 秒级K线存在时用于细化K线内价格路径
+        let mut sub_bars = Map::new();
+        if sub_bar && interval_dur > Duration::seconds(1) {
+            for code in codes {
+                if let Some(data) =
+                    data_source.sub_bars(code, start_time, end_time + interval_dur)?
+                {
+                    sub_bars.insert(code.clone(), data);
+                }
+            }
+        }
+
+        let mut funding_rates = Map::with_capacity(codes.len());
+        for code in codes {
+            if let Some(rates) = data_source.funding_rates(code, end_time)? {
+                funding_rates.insert(code.clone(), rates);
+            }
+        }
+
         Ok(Self {
             context,
             strategy,
+            interval: interval_dur,
             history_bar_len,
             bar_idx: history_bar_len,
             start_time,
             end_time,
             curr_time: start_time,
-            maker_fee_rate,
-            taker_fee_rate,
+            fee_schedule,
             slippage,
+            participation_rate,
+            liquidation_fee_rate,
+            intrabar_path,
+            latency,
+            sub_bars,
+            bar_prices: Default::default(),
+            bar_sizes: Default::default(),
             init_cash: cash,
             history_equities: Default::default(),
             trades: Default::default(),
+            funding_rates,
+            fundings: Default::default(),
         })
     }
 }
 
 impl Backtest {
+    /// 撮合订单, 返回各交易对本根K线内最后一次成交的价格路径位置
     #[tracing::instrument(skip_all)]
-    fn cross_order(&mut self) -> Result<()> {
-        let orders_by_code: Map<String, Vec<(String, Order)>> = self
+    fn cross_order(&mut self) -> Result<Map<String, Decimal>> {
+        let mut last_fills = Map::new();
+
+        // 下单时已拒绝或等待激活时已撤销的订单, 撮合时通知策略
+        let finished = self
+            .context
+            .orders
+            .values()
+            .filter(|order| matches!(order.status, OrderStatus::Rejected | OrderStatus::Canceled))
+            .map(|order| order.id.clone())
+            .collect::<Vec<_>>();
+        for order_id in finished {
+            if let Some(order) = self.context.orders.get(&order_id).cloned() {
+                self.finish_order(order)?;
+            }
+        }
+
+        let orders_by_code: Map<String, Vec<String>> = self
             .context
             .orders
             .iter()
             .filter(|(_, order)| {
                 matches!(
                     order.status,
-                    OrderStatus::New | OrderStatus::Pending | OrderStatus::Canceling
+                    OrderStatus::New
+                        | OrderStatus::Pending
+                        | OrderStatus::PartiallyFilled
+                        | OrderStatus::Canceling
                 )
             })
-            .map(|(id, order)| (id.clone(), order.clone()))
             .fold(Map::new(), |mut acc, (id, order)| {
-                acc.entry(order.code.clone()).or_default().push((id, order));
+                acc.entry(order.code.clone()).or_default().push(id.clone());
                 acc
             });
 
         for (code, orders) in orders_by_code {
-            let bars = self.context.bars.get(&code).unwrap();
+            let bar_size = *self.bar_sizes.get(&code).unwrap();
+
+            let path = self.bar_path(&code)?;
+
+            // 按价格路径上的成交先后顺序撮合, 无法成交的订单排在最后
+            let mut orders = orders
+                .into_iter()
+                .map(|order_id| {
+                    let pos = self.context.orders.get(&order_id).and_then(|order| {
+                        let mut order = order.clone();
+                        let start = self.order_start(&order, &path)?;
+                        let touch = if !order.type_.is_trigger() || order.triggered {
+                            Some(start)
+                        } else {
+                            trigger_order(&mut order, &path, start)
+                        };
+                        touch
+                            .and_then(|touch| fill_touch(&order, &path, touch))
+                            .map(|(touch, _)| touch.pos)
+                    });
+                    (pos.unwrap_or(Decimal::MAX), order_id)
+                })
+                .collect::<Vec<(Decimal, String)>>();
+            orders.sort_by_key(|(pos, _)| *pos);
+
+            // 本根K线剩余可成交数量
+            let mut remain_size = if self.participation_rate.is_zero() {
+                None
+            } else {
+                let symbol = self.context.symbols.get(&code).unwrap();
+                Some(symbol.trunc_size(bar_size * self.participation_rate))
+            };
 
-            let open_price = Decimal::try_from(df_f64(bars, "open", self.bar_idx)?)?;
-            let high_price = Decimal::try_from(df_f64(bars, "high", self.bar_idx)?)?;
-            let low_price = Decimal::try_from(df_f64(bars, "low", self.bar_idx)?)?;
+            for (_, order_id) in orders {
+                // 回调中可能已撤销或修改订单, 以最新状态为准
+                let Some(mut order) = self.context.orders.get(&order_id).cloned() else {
+                    continue;
+                };
 
-            for (order_id, mut order) in orders {
-                let (can_fill, fill_price) = match order.type_ {
-                    OrderType::Market => {
-                        let price = match order.side {
-                            Side::Buy => open_price * (Decimal::ONE + self.slippage),
-                            Side::Sell => open_price * (Decimal::ONE - self.slippage),
-                        };
-                        (true, price)
-                    }
-                    OrderType::Limit => {
-                        let limit_price = order.price.unwrap();
-                        match order.side {
-                            Side::Buy => {
-                                if limit_price >= open_price {
-                                    // 开盘价匹配，以开盘价成交
-                                    (true, open_price)
-                                } else if limit_price >= low_price {
-                                    // 盘中匹配，以限价成交
-                                    (true, limit_price)
-                                } else {
-                                    (false, Decimal::ZERO)
-                                }
-                            }
-                            Side::Sell => {
-                                if limit_price <= open_price {
-                                    // 开盘价匹配，以开盘价成交
-                                    (true, open_price)
-                                } else if limit_price <= high_price {
-                                    // 盘中匹配，以限价成交
-                                    (true, limit_price)
-                                } else {
-                                    (false, Decimal::ZERO)
-                                }
-                            }
-                        }
+                if !matches!(
+                    order.status,
+                    OrderStatus::New
+                        | OrderStatus::Pending
+                        | OrderStatus::PartiallyFilled
+                        | OrderStatus::Canceling
+                ) {
+                    continue;
+                }
+
+                let orig_status = order.status;
+
+                // 撤单在本根K线内生效的路径位置
+                let cancel_pos = order
+                    .cancel_time
+                    .filter(|time| *time < self.curr_time + self.interval)
+                    .map(|time| self.time_pos(&path, time));
+
+                // 下单延迟未到: 订单尚未生效
+                let Some(start) = self.order_start(&order, &path) else {
+                    if cancel_pos.is_some() {
+                        order.status = OrderStatus::Canceled;
+                        self.finish_order(order)?;
                     }
+                    continue;
                 };
 
-                if can_fill {
-                    let orig_status = order.status;
+                // 条件单先检查触发, 触发后以触发价格作为撮合参考价格
+                let was_triggered = !order.type_.is_trigger() || order.triggered;
+                let ref_touch = if was_triggered {
+                    Some(start)
+                } else {
+                    let touch = trigger_order(&mut order, &path, start);
+                    order.triggered = touch.is_some();
+                    touch
+                };
+
+                // 首次参与撮合(新订单或刚触发的条件单)
+                let is_fresh =
+                    ref_touch.is_some() && (orig_status == OrderStatus::New || !was_triggered);
+
+                // crossed: 以参考价格立即成交; 撤单生效后不再成交
+                let (can_fill, fill_at, crossed) =
+                    match ref_touch.and_then(|touch| fill_touch(&order, &path, touch)) {
+                        Some((touch, crossed))
+                            if cancel_pos.is_none_or(|cancel_pos| touch.pos < cancel_pos) =>
+                        {
+                            (true, Some(touch), crossed)
+                        }
+                        _ => (false, None, false),
+                    };
 
-                    order.filled = order.size;
-                    order.status = OrderStatus::Filled;
+                // 只做挂单: 首次撮合即可成交时拒绝
+                if is_fresh && order.post_only && crossed {
+                    order.status = OrderStatus::Rejected;
+                    order.reason = Some(RejectReason::PostOnly);
+                    self.finish_order(order)?;
+                    continue;
+                }
 
-                    let pos = self.context.positions.get_mut(&order.code).unwrap();
+                // IOC/FOK 仅在首次撮合时以参考价格成交, 剩余部分取消
+                let expire = is_fresh && order.time_in_force != TimeInForce::Gtc;
+                let can_fill = can_fill && (!expire || crossed);
 
-                    // 根据方向获取对应的 price/size
-                    let (old_pos_price, old_pos_size) = match order.direction {
-                        Direction::Long => (pos.long.price, pos.long.size),
-                        Direction::Short => (pos.short.price, pos.short.size),
-                    };
+                // 只减仓: 成交数量不超过当前持仓(单向持仓按买卖方向确定平仓方向)
+                let close_direction = if self.context.is_one_way() {
+                    order.side.close_direction()
+                } else {
+                    order.direction
+                };
+                let pos = self.context.positions.get(&order.code).unwrap();
+                let pos_size = pos.get(close_direction).size;
+                let reduce_done = order.reduce_only && pos_size.is_zero();
+
+                let unfill_size = order.size - order.filled;
+                let mut fill_size = match remain_size {
+                    Some(remain) => unfill_size.min(remain),
+                    None => unfill_size,
+                };
+                if order.reduce_only {
+                    fill_size = fill_size.min(pos_size);
+                }
+                let can_fill = can_fill
+                    && fill_size > Decimal::ZERO
+                    && !(expire
+                        && order.time_in_force == TimeInForce::Fok
+                        && fill_size < unfill_size);
 
-                    let cash = fill_price * order.size;
+                if can_fill {
+                    if let Some(remain) = remain_size.as_mut() {
+                        *remain -= fill_size;
+                    }
 
-                    // 手续费率：挂单 vs 吃单
-                    let fee_rate = if orig_status == OrderStatus::Pending {
-                        self.maker_fee_rate
+                    let Touch {
+                        pos: fill_pos,
+                        price: fill_price,
+                    } = fill_at.unwrap();
+                    let last_fill = last_fills.entry(code.clone()).or_insert(fill_pos);
+                    *last_fill = fill_pos.max(*last_fill);
+
+                    // 市价类订单按滑点模型调整成交价格
+                    // 成交价格按最小价格变动向对交易者不利的方向取整, 取整差额计入滑点
+                    let (fill_price, slippage_cash) = if order.type_.is_limit() {
+                        let symbol = self.context.symbols.get(&order.code).unwrap();
+                        let price = symbol.adverse_price(fill_price, order.side);
+                        let slippage_cash = (symbol.calc_cash(price, fill_size)
+                            - symbol.calc_cash(fill_price, fill_size))
+                        .abs();
+                        (price, slippage_cash)
                     } else {
-                        self.taker_fee_rate
+                        let rate = self
+                            .calc_slippage(&order, fill_price, fill_size)?
+                            .max(Decimal::ZERO);
+                        let price = match order.side {
+                            Side::Buy => fill_price * (Decimal::ONE + rate),
+                            Side::Sell => fill_price * (Decimal::ONE - rate),
+                        };
+                        let symbol = self.context.symbols.get(&order.code).unwrap();
+                        let price = symbol.adverse_price(price, order.side);
+                        let slippage_cash = (symbol.calc_cash(price, fill_size)
+                            - symbol.calc_cash(fill_price, fill_size))
+                        .abs();
+                        (price, slippage_cash)
                     };
 
-                    let fee_cash = cash * fee_rate;
-
-                    let mut rpl = Decimal::ZERO;
-                    let new_pos_size;
-                    let mut new_pos_price = old_pos_price;
-
-                    // 合约交易逻辑
-                    // Long+Buy=做多开仓, Long+Sell=做多平仓
-                    // Short+Sell=做空开仓, Short+Buy=做空平仓
-                    let is_open = matches!(
-                        (order.direction, order.side),
-                        (Direction::Long, Side::Buy) | (Direction::Short, Side::Sell)
-                    );
-
-                    if is_open {
-                        // 开仓
-                        new_pos_size = old_pos_size + order.size;
-                        if old_pos_size > Decimal::ZERO {
-                            new_pos_price = (old_pos_price * old_pos_size
-                                + fill_price * order.size)
-                                / new_pos_size;
-                        } else {
-                            new_pos_price = fill_price;
-                        }
-                        self.context.cash -= fee_cash;
+                    order.filled += fill_size;
+                    order.status = if order.filled >= order.size {
+                        OrderStatus::Filled
+                    } else if cancel_pos.is_some() || expire {
+                        // 撤单已生效或IOC的订单部分成交后, 剩余部分直接取消
+                        OrderStatus::Canceled
+                    } else if orig_status == OrderStatus::Canceling {
+                        OrderStatus::Canceling
                     } else {
-                        // 平仓
-                        rpl = match order.direction {
-                            Direction::Long => (fill_price - old_pos_price) * order.size - fee_cash,
-                            Direction::Short => {
-                                (old_pos_price - fill_price) * order.size - fee_cash
-                            }
-                        };
-                        new_pos_size = old_pos_size - order.size;
-                        self.context.cash += rpl;
-                    }
+                        OrderStatus::PartiallyFilled
+                    };
 
-                    // 更新持仓
-                    match order.direction {
-                        Direction::Long => {
-                            pos.long.price = new_pos_price;
-                            pos.long.size = new_pos_size;
-                        }
-                        Direction::Short => {
-                            pos.short.price = new_pos_price;
-                            pos.short.size = new_pos_size;
+                    let symbol = self.context.symbols.get(&order.code).unwrap();
+                    let cash = symbol.calc_cash(fill_price, fill_size);
+
+                    // 手续费：限价单挂单后成交为挂单, 立即成交为吃单; 挂单返佣时为负数
+                    let is_maker = order.type_.is_limit() && !(is_fresh && crossed);
+                    let fee_cash =
+                        self.fee_schedule
+                            .calc_fee(self.curr_time, &order.code, cash, is_maker);
+
+                    // 单向持仓: 先平反向持仓, 剩余部分反向开仓; 手续费和滑点按数量分摊
+                    let legs = if self.context.is_one_way() {
+                        let close_size = fill_size.min(pos_size);
+                        [
+                            (order.side.close_direction(), close_size),
+                            (order.side.open_direction(), fill_size - close_size),
+                        ]
+                    } else {
+                        [
+                            (order.direction, fill_size),
+                            (order.direction, Decimal::ZERO),
+                        ]
+                    };
+                    for (direction, size) in legs {
+                        if size.is_zero() {
+                            continue;
                         }
+                        let ratio = size / fill_size;
+                        self.fill_position(
+                            &order,
+                            direction,
+                            fill_price,
+                            size,
+                            fee_cash * ratio,
+                            slippage_cash * ratio,
+                        );
                     }
 
-                    let trade = Trade {
-                        id: order_id.clone(),
-                        time: self.curr_time,
-                        code: order.code.clone(),
-                        direction: order.direction,
-                        side: order.side,
-                        price: fill_price,
-                        size: order.size,
-                        fee: fee_cash,
-                        rpl,
-                    };
-                    self.trades.push(trade);
+                    // 只减仓订单在持仓平完后取消剩余部分
+                    let pos = self.context.positions.get(&order.code).unwrap();
+                    if order.reduce_only
+                        && order.status == OrderStatus::PartiallyFilled
+                        && pos.get(close_direction).size.is_zero()
+                    {
+                        order.status = OrderStatus::Canceled;
+                    }
 
                     let order_code = order.code.clone();
+                    let is_done =
+                        matches!(order.status, OrderStatus::Filled | OrderStatus::Canceled);
                     self.context.orders.insert(order_id.clone(), order);
 
                     unsafe {
@@ -242,14 +478,26 @@ impl Backtest {
                         self.strategy.on_position(&mut *this, &order_code)?;
                     }
 
-                    self.context.orders.swap_remove(&order_id);
+                    self.cancel_oco(&order_id)?;
+
+                    if is_done {
+                        self.archive_order(&order_id)?;
+                    }
                 } else {
-                    let order = self.context.orders.get_mut(&order_id).unwrap();
                     let should_remove = match order.status {
-                        OrderStatus::Canceling => {
+                        _ if cancel_pos.is_some() => {
                             order.status = OrderStatus::Canceled;
                             true
                         }
+                        _ if expire || reduce_done => {
+                            order.status = OrderStatus::Canceled;
+                            true
+                        }
+                        // 改单后重新排队的订单已有成交时仍为部分成交
+                        OrderStatus::New if order.filled > Decimal::ZERO => {
+                            order.status = OrderStatus::PartiallyFilled;
+                            false
+                        }
                         OrderStatus::New => {
                             order.status = OrderStatus::Pending;
                             false
@@ -258,133 +506,134 @@ impl Backtest {
                     };
 
                     if should_remove {
-                        unsafe {
-                            let this = self as *mut Backtest;
-                            self.strategy.on_order(&mut *this, &order_id)?;
-                        }
-                        self.context.orders.swap_remove(&order_id);
+                        self.finish_order(order)?;
+                    } else {
+                        self.context.orders.insert(order_id.clone(), order);
                     }
                 }
             }
         }
 
-        Ok(())
-    }
-}
-
-impl Engine for Backtest {
-    fn get_context(&self) -> &Context {
-        &self.context
+        Ok(last_fills)
     }
 
-    fn get_time(&self) -> Time {
-        self.curr_time
-    }
+    /// 按成交更新持仓、资金及逐仓保证金, 并记录成交
+    fn fill_position(
+        &mut self,
+        order: &Order,
+        direction: Direction,
+        fill_price: Decimal,
+        fill_size: Decimal,
+        fee_cash: Decimal,
+        slippage_cash: Decimal,
+    ) {
+        let symbol = self.context.symbols.get(&order.code).unwrap();
+        let cash = symbol.calc_cash(fill_price, fill_size);
+        let pos = self.context.positions.get(&order.code).unwrap();
+        let dir_pos = pos.get(direction);
+        let (old_pos_price, old_pos_size) = (dir_pos.price, dir_pos.size);
+
+        let mut rpl = Decimal::ZERO;
+        let new_pos_size;
+        let mut new_pos_price = old_pos_price;
+        let mut entry_tags = Vec::new();
+
+        // 合约交易逻辑
+        // Long+Buy=做多开仓, Long+Sell=做多平仓
+        // Short+Sell=做空开仓, Short+Buy=做空平仓
+        let is_open = matches!(
+            (direction, order.side),
+            (Direction::Long, Side::Buy) | (Direction::Short, Side::Sell)
+        );
 
-    fn get_bars(&self, code: &str, all: bool) -> Result<DataFrame> {
-        let bars = self
-            .context
-            .bars
-            .get(code)
-            .ok_or_else(|| anyhow!("bars not found: {}", code))?;
-        if all {
-            Ok(bars.clone())
+        if is_open {
+            // 开仓
+            new_pos_size = old_pos_size + fill_size;
+            new_pos_price =
+                symbol.calc_avg_price(old_pos_price, old_pos_size, fill_price, fill_size);
+            self.context.cash -= fee_cash;
         } else {
-            let end_idx = self.bar_idx.min(bars.height());
-            let hist_len = if self.history_bar_len == 0 {
-                10
-            } else {
-                self.history_bar_len
-            };
-            let start_idx = end_idx.saturating_sub(hist_len);
-            let length = end_idx - start_idx;
-            let mut result = bars.slice(start_idx as i64, length);
-            if result.should_rechunk() {
-                result.rechunk_mut();
-            }
-            Ok(result)
+            // 平仓
+            rpl = symbol.calc_pnl(direction, old_pos_price, fill_price, fill_size) - fee_cash;
+            new_pos_size = old_pos_size - fill_size;
+            self.context.cash += rpl;
         }
-    }
 
-    fn get_signals(&self) -> DataFrame {
-        let signals_height = self.context.signals.height();
-        if signals_height == 0 || self.bar_idx == 0 {
-            return Default::default();
-        }
-        let end_idx = self.bar_idx.min(signals_height);
-        let hist_len = if self.history_bar_len == 0 {
-            10
+        // 更新持仓
+        let pos = self.context.positions.get_mut(&order.code).unwrap();
+        let lever = pos.lever;
+        let is_isolated = pos.is_isolated();
+        let dir_pos = pos.get_mut(direction);
+        dir_pos.price = new_pos_price;
+        dir_pos.size = new_pos_size;
+        if is_open {
+            dir_pos.open_tag(&order.tag, fill_size);
         } else {
-            self.history_bar_len
-        };
-        let start_idx = end_idx.saturating_sub(hist_len);
-        let length = end_idx - start_idx;
-        let mut result = self.context.signals.slice(start_idx as i64, length);
-        if result.should_rechunk() {
-            result.rechunk_mut();
+            entry_tags = dir_pos.close_tags(fill_size);
         }
-        result
-    }
 
-    fn set_signals(&mut self, mut signals: DataFrame) -> Result<()> {
-        let req_len = self
-            .context
-            .bars
-            .values()
-            .next()
-            .ok_or_else(|| anyhow!("bars not found"))?
-            .height();
-        ensure!(signals.height() == req_len);
-        if signals.should_rechunk() {
-            signals.rechunk_mut();
+        // 逐仓保证金: 开仓按杠杆从资金划入, 平仓按比例划回资金
+        if is_isolated {
+            let margin_change = if is_open {
+                cash / lever
+            } else if new_pos_size.is_zero() {
+                -dir_pos.margin
+            } else {
+                -dir_pos.margin * fill_size / old_pos_size
+            };
+            dir_pos.margin += margin_change;
+            self.context.cash -= margin_change;
         }
-        self.context.signals = signals;
-        Ok(())
-    }
 
-    fn set_lever(&mut self, code: &str, lever: u32) -> Result<()> {
-        let symbol = self
-            .context
-            .symbols
-            .get(code)
-            .ok_or_else(|| anyhow!("symbol not found: {}", code))?;
-        let lever = Decimal::from(lever);
-        ensure!(lever >= Decimal::ONE && lever <= symbol.max_lever);
-        let pos = self.context.positions.get_mut(code).unwrap();
-        pos.lever = lever;
-        Ok(())
+        let trade = Trade {
+            id: order.id.clone(),
+            time: self.curr_time,
+            code: order.code.clone(),
+            direction,
+            side: order.side,
+            price: fill_price,
+            size: fill_size,
+            fee: fee_cash,
+            slippage: slippage_cash,
+            rpl,
+            tag: order.tag.clone(),
+            entry_tags,
+        };
+        self.context.add_trade(trade.clone());
+        self.trades.push(trade);
     }
 
-    fn place_order(
-        &mut self,
+    /// 下单检查: 返回拒绝原因, 只减仓订单数量截断为可用持仓
+    fn check_order(
+        &self,
         code: &str,
         type_: OrderType,
         direction: Direction,
         side: Side,
-        size: Decimal,
+        size: &mut Decimal,
         price: Option<Decimal>,
-    ) -> Result<String> {
-        let symbol = self
-            .context
-            .symbols
-            .get(code)
-            .ok_or_else(|| anyhow!("symbol not found: {}", code))?;
-
-        match type_ {
-            OrderType::Market => ensure!(price.is_none(), "market order must not have price"),
-            OrderType::Limit => ensure!(price.is_some(), "limit order must have price"),
+        trigger_price: Option<Decimal>,
+        actual_price: Decimal,
+        post_only: bool,
+        reduce_only: bool,
+    ) -> Option<RejectReason> {
+        let symbol = self.context.symbols.get(code).unwrap();
+
+        // 委托价格和触发价格须为最小价格变动的整数倍
+        if [price, trigger_price]
+            .into_iter()
+            .flatten()
+            .any(|price| !symbol.is_tick_price(price))
+        {
+            return Some(RejectReason::InvalidTick);
+        }
+        if *size < symbol.min_size {
+            return Some(RejectReason::BelowMinSize);
+        }
+        let cash = symbol.calc_cash(actual_price, *size);
+        if cash < symbol.min_cash {
+            return Some(RejectReason::BelowMinNotional);
         }
-
-        let actual_price = match type_ {
-            OrderType::Market => symbol.price,
-            OrderType::Limit => price.ok_or_else(|| anyhow!("limit order must have price"))?,
-        };
-
-        ensure!(actual_price > Decimal::ZERO);
-        ensure!(size >= symbol.min_size);
-
-        let cash = actual_price * size;
-        ensure!(cash >= symbol.min_cash);
 
         // 合约交易资金和持仓检查
         // Long+Buy=做多开仓, Long+Sell=做多平仓
@@ -394,77 +643,1242 @@ impl Engine for Backtest {
             (Direction::Long, Side::Buy) | (Direction::Short, Side::Sell)
         );
 
+        let pos = self.context.positions.get(code).unwrap();
+        let avail_cash = self.context.calc_avail_cash();
         if is_open {
+            if reduce_only {
+                return Some(RejectReason::ReduceOnly);
+            }
+
+            // 开仓后名义价值所在杠杆分层的最大杠杆倍数
+            let pos_cash = symbol.calc_cash(symbol.mark_price, pos.get(direction).size) + cash;
+            if symbol
+                .margin_tier(pos_cash)
+                .is_some_and(|tier| pos.lever > tier.max_lever)
+            {
+                return Some(RejectReason::ExceedMaxLever);
+            }
+
             // 开仓检查资金
-            let pos = self.context.positions.get(code).unwrap();
-            let required_cash = cash / pos.lever;
-            let avail_cash = self.context.calc_avail_cash();
-            ensure!(avail_cash >= required_cash);
+            if avail_cash < cash / pos.lever {
+                return Some(RejectReason::InsufficientMargin);
+            }
         } else {
-            // 平仓检查持仓
+            // 平仓检查持仓, 只减仓订单数量不超过可用持仓
             let avail_size = self.context.calc_pos_avail_size(code, direction);
-            ensure!(avail_size >= size);
+            if reduce_only {
+                *size = (*size).min(avail_size);
+                if size.is_zero() {
+                    return Some(RejectReason::ReduceOnly);
+                }
+            } else if self.context.is_one_way() && *size > avail_size {
+                // 单向持仓: 超出可用持仓的部分反向开仓, 检查资金
+                if avail_cash < symbol.calc_cash(actual_price, *size - avail_size) / pos.lever {
+                    return Some(RejectReason::InsufficientMargin);
+                }
+            } else if avail_size < *size {
+                return Some(RejectReason::InsufficientPosition);
+            }
         }
 
-        let order_id = id_new();
-        let order = Order {
-            id: order_id.clone(),
-            code: code.to_string(),
-            type_,
-            direction,
-            side,
-            price,
-            size,
-            filled: Decimal::ZERO,
-            status: OrderStatus::New,
-            time: self.curr_time,
-        };
-
-        self.context.orders.insert(order_id.clone(), order);
+        // 只做挂单: 按当前价格即可成交时拒绝
+        if post_only && type_ == OrderType::Limit {
+            let limit_price = price.unwrap();
+            let crossed = match side {
+                Side::Buy => limit_price >= symbol.price,
+                Side::Sell => limit_price <= symbol.price,
+            };
+            if crossed {
+                return Some(RejectReason::PostOnly);
+            }
+        }
 
-        Ok(order_id)
+        None
     }
 
-    fn cancel_order(&mut self, id: &str) -> Result<()> {
-        let Some(order) = self.context.orders.get_mut(id) else {
-            return Ok(());
+    /// 计算滑点比例: 策略自定义滑点优先, 否则使用滑点模型
+    fn calc_slippage(&mut self, order: &Order, price: Decimal, size: Decimal) -> Result<Decimal> {
+        let custom = unsafe {
+            let this = self as *mut Backtest;
+            self.strategy.on_slippage(&mut *this, order, price, size)?
         };
+        if let Some(rate) = custom {
+            return Ok(rate);
+        }
 
-        ensure!(matches!(
-            order.status,
-            OrderStatus::New | OrderStatus::Pending | OrderStatus::Canceling
-        ));
+        let bars = self.context.bars.get(&order.code).unwrap();
+        self.slippage.calc(order, price, size, bars, self.bar_idx)
+    }
 
-        order.status = OrderStatus::Canceling;
+    /// 时间在当前K线价格路径上的位置(按时间线性映射)
+    fn time_pos(&self, path: &[Decimal], time: Time) -> Decimal {
+        let elapsed = (time - self.curr_time).num_milliseconds();
+        let total = self.interval.num_milliseconds();
+        if elapsed <= 0 || total <= 0 {
+            return Decimal::ZERO;
+        }
 
-        Ok(())
+        let segments = Decimal::from(path.len().saturating_sub(1));
+        (Decimal::from(elapsed.min(total)) / Decimal::from(total)) * segments
     }
-}
 
-impl Backtest {
-    #[tracing::instrument(name = "backtest", skip_all)]
-    pub fn run(&mut self) -> Result<Report> {
-        let codes = self
-            .context
-            .symbols
-            .keys()
-            .cloned()
-            .collect::<Vec<String>>();
+    /// 订单在当前K线价格路径上的生效点, 本根K线内尚未生效时返回 None
+    fn order_start(&self, order: &Order, path: &[Decimal]) -> Option<Touch> {
+        if order.active_time >= self.curr_time + self.interval {
+            return None;
+        }
+        Some(Touch::at(path, self.time_pos(path, order.active_time)))
+    }
 
-        for code in &codes {
-            let bars_df = self.context.bars.get(code).unwrap();
-            let open_price = Decimal::try_from(df_f64(bars_df, "open", self.bar_idx)?)?;
+    /// 当前K线的价格路径, 有秒级K线时按秒级K线逐根展开
+    fn bar_path(&self, code: &str) -> Result<Vec<Decimal>> {
+        let pos = self.context.positions.get(code).unwrap();
+        let net_size = pos.long.size - pos.short.size;
+
+        if let Some(sub_bars) = self.sub_bars.get(code) {
+            let start_ms = self.curr_time.timestamp_millis();
+            let end_ms = start_ms + self.interval.num_milliseconds();
+            let prices = sub_bars.range(start_ms, end_ms);
+            if !prices.is_empty() {
+                let symbol = self.context.symbols.get(code).unwrap();
+                let to_price = |v: f64| Ok::<_, anyhow::Error>(symbol.round_price(v.try_into()?));
+                let mut path = Vec::with_capacity(prices.len() * 4);
+                for [open, high, low, close] in prices {
+                    path.extend(price_path(
+                        self.intrabar_path,
+                        net_size,
+                        to_price(*open)?,
+                        to_price(*high)?,
+                        to_price(*low)?,
+                        to_price(*close)?,
+                    ));
+                }
+                return Ok(path);
+            }
+        }
+
+        let [open, high, low, close] = self.bar_prices.get(code).unwrap();
+        Ok(price_path(self.intrabar_path, net_size, *open, *high, *low, *close).to_vec())
+    }
+
+    /// 读取当前K线价格并按最小价格变动取整, 以开盘价更新最新价格和标记价格
+    fn update_bar_prices(&mut self) -> Result<()> {
+        for (code, bars) in &self.context.bars {
             let symbol = self.context.symbols.get_mut(code).unwrap();
-            symbol.mark_price = open_price;
-            symbol.price = open_price;
+            let mut prices = [Decimal::ZERO; 4];
+            for (price, name) in prices.iter_mut().zip(["open", "high", "low", "close"]) {
+                *price = symbol.round_price(Decimal::try_from(df_f64(bars, name, self.bar_idx)?)?);
+            }
+            symbol.mark_price = prices[0];
+            symbol.price = prices[0];
+            self.bar_prices.insert(code.clone(), prices);
+            let size = Decimal::try_from(df_f64(bars, "size", self.bar_idx)?)?;
+            self.bar_sizes.insert(code.clone(), size);
         }
+        Ok(())
+    }
 
-        self.history_equities
-            .push(self.context.calc_equity().to_f64().unwrap_or(0.0));
+    /// 结束订单(已成交/已取消/已拒绝): 通知策略并移除
+    fn finish_order(&mut self, order: Order) -> Result<()> {
+        let order_id = order.id.clone();
+        self.context.orders.insert(order_id.clone(), order);
 
         unsafe {
             let this = self as *mut Backtest;
-            self.strategy.on_start(&mut *this)?;
+            self.strategy.on_order(&mut *this, &order_id)?;
+        }
+
+        self.archive_order(&order_id)
+    }
+
+    /// 已完成订单移入历史订单, 等待中的子订单按父订单成交数量激活, 未成交时撤销
+    fn archive_order(&mut self, order_id: &str) -> Result<()> {
+        let filled = self
+            .context
+            .orders
+            .get(order_id)
+            .map(|order| order.filled)
+            .unwrap_or_default();
+        self.context.archive_order(order_id);
+
+        let children = self
+            .context
+            .orders
+            .values()
+            .filter(|order| {
+                order.status == OrderStatus::Waiting && order.parent_id.as_deref() == Some(order_id)
+            })
+            .map(|order| order.id.clone())
+            .collect::<Vec<_>>();
+        for child_id in children {
+            let Some(mut child) = self.context.orders.get(&child_id).cloned() else {
+                continue;
+            };
+            if filled.is_zero() {
+                child.status = OrderStatus::Canceled;
+                self.finish_order(child)?;
+            } else {
+                child.status = OrderStatus::New;
+                child.size = filled;
+                child.active_time = self.curr_time;
+                self.context.orders.insert(child_id, child);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// OCO订单成交后撤销关联订单
+    fn cancel_oco(&mut self, order_id: &str) -> Result<()> {
+        let Some(oco_id) = self
+            .context
+            .orders
+            .get(order_id)
+            .and_then(|order| order.oco_id.clone())
+        else {
+            return Ok(());
+        };
+        let Some(mut sibling) = self.context.orders.get(&oco_id).cloned() else {
+            return Ok(());
+        };
+        if !matches!(
+            sibling.status,
+            OrderStatus::New
+                | OrderStatus::Pending
+                | OrderStatus::PartiallyFilled
+                | OrderStatus::Canceling
+                | OrderStatus::Waiting
+        ) {
+            return Ok(());
+        }
+        sibling.status = OrderStatus::Canceled;
+        self.finish_order(sibling)
+    }
+
+    /// 检查强平: 价格路径触及强平价格时按强平价格平仓
+    ///
+    /// 本根K线内有成交的交易对只检查最后一次成交之后的价格路径
+    #[tracing::instrument(skip_all)]
+    fn check_liquidation(&mut self, last_fills: &Map<String, Decimal>) -> Result<()> {
+        let codes = self
+            .context
+            .symbols
+            .keys()
+            .cloned()
+            .collect::<Vec<String>>();
+
+        for code in codes {
+            for direction in [Direction::Long, Direction::Short] {
+                let liq_price = self.context.calc_liq_price(&code, direction);
+
+                let pos = self.context.positions.get_mut(&code).unwrap();
+                let dir_pos = match direction {
+                    Direction::Long => &mut pos.long,
+                    Direction::Short => &mut pos.short,
+                };
+                dir_pos.liq_price = liq_price;
+
+                if dir_pos.size.is_zero() {
+                    continue;
+                }
+
+                let path = self.bar_path(&code)?;
+                let start = Touch::at(&path, last_fills.get(&code).copied().unwrap_or_default());
+
+                // 起点已越过强平价格(如开盘跳空)时以起点价格成交
+                let below = direction == Direction::Long;
+                let Some(Touch {
+                    price: fill_price, ..
+                }) = path_touch(&path, start, liq_price, below)
+                else {
+                    continue;
+                };
+
+                // 强平价格按最小价格变动向不利方向取整
+                let symbol = self.context.symbols.get(&code).unwrap();
+                let close_side = match direction {
+                    Direction::Long => Side::Sell,
+                    Direction::Short => Side::Buy,
+                };
+                let fill_price = symbol.adverse_price(fill_price, close_side);
+
+                self.liquidate(&code, direction, fill_price)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// 强制平仓: 撤销该交易对的未完成订单(逐仓仅撤销该方向), 并按指定价格平掉方向持仓
+    fn liquidate(&mut self, code: &str, direction: Direction, price: Decimal) -> Result<()> {
+        let is_isolated = self.context.positions.get(code).unwrap().is_isolated();
+        let order_ids = self
+            .context
+            .orders
+            .values()
+            .filter(|order| {
+                order.code == code
+                    && order.status != OrderStatus::Rejected
+                    && (!is_isolated || order.direction == direction)
+            })
+            .map(|order| order.id.clone())
+            .collect::<Vec<String>>();
+
+        for order_id in order_ids {
+            let Some(mut order) = self.context.orders.get(&order_id).cloned() else {
+                continue;
+            };
+            order.status = OrderStatus::Canceled;
+            self.finish_order(order)?;
+        }
+
+        let symbol = self.context.symbols.get(code).unwrap();
+        let pos = self.context.positions.get_mut(code).unwrap();
+        let dir_pos = match direction {
+            Direction::Long => &mut pos.long,
+            Direction::Short => &mut pos.short,
+        };
+
+        let size = dir_pos.size;
+        let fee_cash = symbol.calc_cash(price, size) * self.liquidation_fee_rate;
+        let mut rpl = symbol.calc_pnl(direction, dir_pos.price, price, size) - fee_cash;
+
+        // 逐仓亏损以该持仓保证金为限
+        let margin = dir_pos.margin;
+        if is_isolated {
+            rpl = rpl.max(-margin);
+        }
+
+        let entry_tags = dir_pos.close_tags(size);
+        *dir_pos = Default::default();
+        self.context.cash += margin + rpl;
+
+        tracing::trace!("强制平仓: {code} {direction} 价格={price} 数量={size} 盈亏={rpl}");
+
+        let trade = Trade {
+            id: id_new(),
+            time: self.curr_time,
+            code: code.to_string(),
+            direction,
+            side: match direction {
+                Direction::Long => Side::Sell,
+                Direction::Short => Side::Buy,
+            },
+            price,
+            size,
+            fee: fee_cash,
+            slippage: Decimal::ZERO,
+            rpl,
+            tag: None,
+            entry_tags,
+        };
+        self.context.add_trade(trade.clone());
+        self.trades.push(trade);
+
+        unsafe {
+            let this = self as *mut Backtest;
+            self.strategy.on_liquidation(&mut *this, code, direction)?;
+            self.strategy.on_position(&mut *this, code)?;
+        }
+
+        Ok(())
+    }
+
+    /// 取出 (prev_time, 当前时间] 内到期的全部资金费率(结算时间, 费率)
+    fn due_funding_rates(&mut self, code: &str, prev_time: Time) -> Result<Vec<(Time, Decimal)>> {
+        let mut due = Vec::new();
+        match self.funding_rates.get_mut(code) {
+            Some(rates) => {
+                while let Some((time, _)) = rates.front()
+                    && *time <= self.curr_time
+                {
+                    due.extend(rates.pop_front());
+                }
+            }
+            None => {
+                // 没有历史资金费率时, 按交易对结算间隔使用当前资金费率, 每个跨过的结算时间结算一次
+                let symbol = self.context.symbols.get(code).unwrap();
+                let interval = symbol.funding_interval as i64 * 3600;
+                if interval > 0 {
+                    let first = prev_time.timestamp().div_euclid(interval) + 1;
+                    let last = self.curr_time.timestamp().div_euclid(interval);
+                    for idx in first..=last {
+                        let time = time_from_millis(idx * interval * 1000)?;
+                        due.push((time, symbol.funding_rate));
+                    }
+                }
+            }
+        }
+        Ok(due)
+    }
+
+    #[tracing::instrument(skip_all)]
+    fn settle_funding(&mut self) -> Result<()> {
+        let codes = self
+            .context
+            .symbols
+            .keys()
+            .cloned()
+            .collect::<Vec<String>>();
+
+        let prev_time = self.curr_time - self.interval;
+        for code in codes {
+            for (time, rate) in self.due_funding_rates(&code, prev_time)? {
+                let symbol = self.context.symbols.get_mut(&code).unwrap();
+                symbol.funding_rate = rate;
+
+                let pos = self.context.positions.get(&code).unwrap();
+                let size = pos.long.size - pos.short.size;
+                let is_isolated = pos.is_isolated();
+                // 全仓按净持仓结算, 逐仓按各方向持仓分别结算
+                let has_pos = if is_isolated {
+                    !pos.long.size.is_zero() || !pos.short.size.is_zero()
+                } else {
+                    !size.is_zero()
+                };
+                if !has_pos {
+                    continue;
+                }
+
+                // 资金费率为正时多头支付空头, 为负时空头支付多头
+                let price = symbol.mark_price;
+                let cash = symbol.calc_cash(price, size) * rate;
+                if is_isolated {
+                    // 逐仓: 资金费计入该方向保证金, 并重新计算强平价格
+                    let dir_cashes = [
+                        (
+                            Direction::Long,
+                            symbol.calc_cash(price, pos.long.size) * rate,
+                        ),
+                        (
+                            Direction::Short,
+                            -symbol.calc_cash(price, pos.short.size) * rate,
+                        ),
+                    ];
+                    for (direction, dir_cash) in dir_cashes {
+                        let pos = self.context.positions.get_mut(&code).unwrap();
+                        let dir_pos = pos.get_mut(direction);
+                        if dir_pos.size.is_zero() {
+                            continue;
+                        }
+                        dir_pos.margin -= dir_cash;
+                        let liq_price = self.context.calc_liq_price(&code, direction);
+                        let pos = self.context.positions.get_mut(&code).unwrap();
+                        pos.get_mut(direction).liq_price = liq_price;
+                    }
+                } else {
+                    self.context.cash -= cash;
+                }
+
+                self.fundings.push(Funding {
+                    time,
+                    code: code.clone(),
+                    rate,
+                    price,
+                    size,
+                    cash,
+                });
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// 价格路径上的触及点
+#[derive(Debug, Clone, Copy)]
+struct Touch {
+    /// 路径位置(线段索引 + 线段内比例)
+    pos: Decimal,
+    /// 触及价格
+    price: Decimal,
+}
+
+impl Touch {
+    /// 路径上指定位置(线性插值价格)
+    fn at(path: &[Decimal], pos: Decimal) -> Self {
+        let idx = pos.floor().to_usize().unwrap_or_default();
+        if idx + 1 >= path.len() {
+            return Self {
+                pos,
+                price: path[path.len() - 1],
+            };
+        }
+
+        let frac = pos - pos.floor();
+        Self {
+            pos,
+            price: path[idx] + (path[idx + 1] - path[idx]) * frac,
+        }
+    }
+}
+
+/// 按路径模型展开单根K线的价格路径
+fn price_path(
+    mode: IntrabarPath,
+    net_size: Decimal,
+    open: Decimal,
+    high: Decimal,
+    low: Decimal,
+    close: Decimal,
+) -> [Decimal; 4] {
+    let nearest_high = high - open <= open - low;
+    let high_first = match mode {
+        IntrabarPath::Ohlc => true,
+        IntrabarPath::Olhc => false,
+        IntrabarPath::Nearest => nearest_high,
+        IntrabarPath::Worst => {
+            if net_size > Decimal::ZERO {
+                false
+            } else if net_size < Decimal::ZERO {
+                true
+            } else {
+                nearest_high
+            }
+        }
+    };
+
+    if high_first {
+        [open, high, low, close]
+    } else {
+        [open, low, high, close]
+    }
+}
+
+/// 价格是否到达目标价格(below: 向下到达, 否则向上到达)
+#[inline]
+fn reached(price: Decimal, target: Decimal, below: bool) -> bool {
+    if below {
+        price <= target
+    } else {
+        price >= target
+    }
+}
+
+/// 从指定位置开始沿路径查找价格首次到达目标价格的位置
+fn path_touch(path: &[Decimal], from: Touch, target: Decimal, below: bool) -> Option<Touch> {
+    if reached(from.price, target, below) {
+        return Some(from);
+    }
+
+    let start = from.pos.floor().to_usize().unwrap_or_default();
+    for idx in start..path.len().saturating_sub(1) {
+        let (a, b) = (path[idx], path[idx + 1]);
+        if reached(b, target, below) {
+            let pos = Decimal::from(idx) + (a - target) / (a - b);
+            return Some(Touch {
+                pos: pos.max(from.pos),
+                price: target,
+            });
+        }
+    }
+
+    None
+}
+
+/// 沿价格路径检查条件单是否在本根K线触发, 返回触发点
+///
+/// 开盘价已越过触发价格时以开盘价触发, 否则以触发价格触发。
+/// 追踪止损单沿路径更新追踪极值价格, 回撤达到回调比例时触发。
+fn trigger_order(order: &mut Order, path: &[Decimal], start: Touch) -> Option<Touch> {
+    match (order.type_, order.side) {
+        // 卖出止损 / 买入止盈: 价格下跌触发
+        (OrderType::StopMarket | OrderType::StopLimit, Side::Sell)
+        | (OrderType::TakeProfitMarket | OrderType::TakeProfitLimit, Side::Buy) => {
+            path_touch(path, start, order.trigger_price.unwrap(), true)
+        }
+        // 买入止损 / 卖出止盈: 价格上涨触发
+        (OrderType::StopMarket | OrderType::StopLimit, Side::Buy)
+        | (OrderType::TakeProfitMarket | OrderType::TakeProfitLimit, Side::Sell) => {
+            path_touch(path, start, order.trigger_price.unwrap(), false)
+        }
+        (OrderType::TrailingStop, side) => {
+            let callback_rate = order.callback_rate.unwrap();
+            // 卖出追踪最高价, 回撤触发; 买入追踪最低价, 反弹触发
+            let below = side == Side::Sell;
+
+            let Some(mut trail) = order.trail_price else {
+                // 未激活: 价格到达激活价格后开始追踪, 下一根K线起可触发
+                let activation = order.trigger_price.unwrap();
+                if let Some(touch) = path_touch(path, start, activation, !below) {
+                    let idx = touch.pos.floor().to_usize().unwrap_or_default();
+                    let rest = path[idx + 1..].iter().copied().chain([touch.price]);
+                    order.trail_price = if below { rest.max() } else { rest.min() };
+                }
+                return None;
+            };
+
+            let stop_of = |trail: Decimal| {
+                if below {
+                    trail * (Decimal::ONE - callback_rate)
+                } else {
+                    trail * (Decimal::ONE + callback_rate)
+                }
+            };
+
+            if reached(start.price, stop_of(trail), below) {
+                return Some(start);
+            }
+
+            let first = start.pos.floor().to_usize().unwrap_or_default();
+            for idx in first..path.len().saturating_sub(1) {
+                // 起点所在线段从生效点开始
+                let (a, a_pos) = if idx == first {
+                    (start.price, start.pos)
+                } else {
+                    (path[idx], Decimal::from(idx))
+                };
+                let b = path[idx + 1];
+                trail = if below { trail.max(a) } else { trail.min(a) };
+                let stop = stop_of(trail);
+                if reached(b, stop, below) {
+                    order.trail_price = Some(trail);
+                    let b_pos = Decimal::from(idx + 1);
+                    return Some(Touch {
+                        pos: a_pos + (a - stop) / (a - b) * (b_pos - a_pos),
+                        price: stop,
+                    });
+                }
+            }
+
+            let last = path[path.len() - 1];
+            order.trail_price = Some(if below {
+                trail.max(last)
+            } else {
+                trail.min(last)
+            });
+            None
+        }
+        (OrderType::Limit | OrderType::Market, _) => Some(start),
+    }
+}
+
+/// 订单生效后的成交点及是否以参考价格立即成交
+fn fill_touch(order: &Order, path: &[Decimal], touch: Touch) -> Option<(Touch, bool)> {
+    if !order.type_.is_limit() {
+        return Some((touch, true));
+    }
+
+    let limit_price = order.price.unwrap();
+    let below = order.side == Side::Buy;
+    if reached(touch.price, limit_price, below) {
+        // 开盘价(触发价)匹配，以开盘价(触发价)成交
+        Some((touch, true))
+    } else {
+        // 盘中匹配，以限价成交
+        path_touch(path, touch, limit_price, below).map(|touch| (touch, false))
+    }
+}
+
+impl Engine for Backtest {
+    fn get_context(&self) -> &Context {
+        &self.context
+    }
+
+    fn get_time(&self) -> Time {
+        self.curr_time
+    }
+
+    fn get_bars(&self, code: &str, all: bool) -> Result<DataFrame> {
+        let bars = self
+            .context
+            .bars
+            .get(code)
+            .ok_or_else(|| anyhow!("bars not found: {}", code))?;
+        if all {
+            Ok(bars.clone())
+        } else {
+            let end_idx = self.bar_idx.min(bars.height());
+            let hist_len = if self.history_bar_len == 0 {
+                10
+            } else {
+                self.history_bar_len
+            };
+            let start_idx = end_idx.saturating_sub(hist_len);
+            let length = end_idx - start_idx;
+            let mut result = bars.slice(start_idx as i64, length);
+            if result.should_rechunk() {
+                result.rechunk_mut();
+            }
+            Ok(result)
+        }
+    }
+
+    fn get_interval_bars(&self, code: &str, interval: &str, n: usize) -> Result<DataFrame> {
+        let bars = self
+            .context
+            .bars
+            .get(code)
+            .ok_or_else(|| anyhow!("bars not found: {}", code))?;
+
+        let interval_dur = interval_from_str(interval)?;
+        let base_secs = self.interval.num_seconds();
+        ensure!(
+            interval_dur.num_seconds() % base_secs == 0,
+            "interval must be a multiple of base interval: {}",
+            interval
+        );
+        let ratio = (interval_dur.num_seconds() / base_secs) as usize;
+
+        let end_idx = self.bar_idx.min(bars.height());
+        let start_idx = end_idx.saturating_sub((n + 1) * ratio);
+        let length = end_idx - start_idx;
+        if length == 0 {
+            return Ok(bars.clear());
+        }
+
+        let df = bars.slice(start_idx as i64, length);
+        let curr_ms = self.curr_time.timestamp_millis();
+        let first_ms = curr_ms - self.interval.num_milliseconds() * length as i64;
+
+        // 只保留完整且已收盘的K线, 避免使用未来数据
+        let time_ms = col("time").cast(DataType::Int64);
+        let mut result = bars::resample(df.lazy(), interval)?
+            .filter(
+                time_ms
+                    .clone()
+                    .gt_eq(lit(first_ms))
+                    .and((time_ms + lit(interval_dur.num_milliseconds())).lt_eq(lit(curr_ms))),
+            )
+            .tail(n as IdxSize)
+            .collect()?;
+
+        if result.should_rechunk() {
+            result.rechunk_mut();
+        }
+
+        Ok(result)
+    }
+
+    fn get_signals(&self) -> DataFrame {
+        let signals_height = self.context.signals.height();
+        if signals_height == 0 || self.bar_idx == 0 {
+            return Default::default();
+        }
+        let end_idx = self.bar_idx.min(signals_height);
+        let hist_len = if self.history_bar_len == 0 {
+            10
+        } else {
+            self.history_bar_len
+        };
+        let start_idx = end_idx.saturating_sub(hist_len);
+        let length = end_idx - start_idx;
+        let mut result = self.context.signals.slice(start_idx as i64, length);
+        if result.should_rechunk() {
+            result.rechunk_mut();
+        }
+        result
+    }
+
+    fn set_signals(&mut self, mut signals: DataFrame) -> Result<()> {
+        let req_len = self
+            .context
+            .bars
+            .values()
+            .next()
+            .ok_or_else(|| anyhow!("bars not found"))?
+            .height();
+        ensure!(signals.height() == req_len);
+        if signals.should_rechunk() {
+            signals.rechunk_mut();
+        }
+        self.context.signals = signals;
+        Ok(())
+    }
+
+    fn set_lever(&mut self, code: &str, lever: u32) -> Result<()> {
+        let symbol = self
+            .context
+            .symbols
+            .get(code)
+            .ok_or_else(|| anyhow!("symbol not found: {}", code))?;
+        let lever = Decimal::from(lever);
+        ensure!(lever >= Decimal::ONE && lever <= symbol.max_lever);
+        let pos = self.context.positions.get_mut(code).unwrap();
+        pos.lever = lever;
+        Ok(())
+    }
+
+    fn set_margin_mode(&mut self, code: &str, mode: MarginMode) -> Result<()> {
+        let pos = self
+            .context
+            .positions
+            .get(code)
+            .ok_or_else(|| anyhow!("position not found: {}", code))?;
+        if pos.margin_mode == mode {
+            return Ok(());
+        }
+
+        ensure!(
+            pos.long.size.is_zero() && pos.short.size.is_zero(),
+            "cannot change margin mode with open position: {code}"
+        );
+        ensure!(
+            !self.context.orders.values().any(|order| order.code == code),
+            "cannot change margin mode with open orders: {code}"
+        );
+
+        let pos = self.context.positions.get_mut(code).unwrap();
+        pos.margin_mode = mode;
+        Ok(())
+    }
+
+    fn add_margin(&mut self, code: &str, direction: Direction, cash: Decimal) -> Result<()> {
+        ensure!(cash > Decimal::ZERO);
+        ensure!(cash <= self.context.calc_avail_cash(), "insufficient cash");
+
+        let pos = self
+            .context
+            .positions
+            .get_mut(code)
+            .ok_or_else(|| anyhow!("position not found: {}", code))?;
+        ensure!(pos.is_isolated(), "position is not isolated: {code}");
+
+        let dir_pos = pos.get_mut(direction);
+        ensure!(!dir_pos.size.is_zero(), "no position: {code} {direction}");
+
+        dir_pos.margin += cash;
+        self.context.cash -= cash;
+        Ok(())
+    }
+
+    fn remove_margin(&mut self, code: &str, direction: Direction, cash: Decimal) -> Result<()> {
+        ensure!(cash > Decimal::ZERO);
+
+        let symbol = self
+            .context
+            .symbols
+            .get(code)
+            .ok_or_else(|| anyhow!("symbol not found: {}", code))?;
+        let upl = self.context.calc_pos_upl(code, direction);
+        let pos = self.context.positions.get_mut(code).unwrap();
+        ensure!(pos.is_isolated(), "position is not isolated: {code}");
+
+        // 减少后保证金(扣除浮亏)不低于初始保证金
+        let lever = pos.lever;
+        let dir_pos = pos.get_mut(direction);
+        let init_margin = symbol.calc_cash(symbol.mark_price, dir_pos.size) / lever;
+        ensure!(
+            dir_pos.margin - cash + upl.min(Decimal::ZERO) >= init_margin,
+            "margin would fall below initial margin: {code} {direction}"
+        );
+
+        dir_pos.margin -= cash;
+        self.context.cash += cash;
+        Ok(())
+    }
+
+    fn submit_order(&mut self, req: OrderRequest) -> Result<String> {
+        let OrderRequest {
+            code,
+            type_,
+            direction,
+            side,
+            size,
+            price,
+            trigger_price,
+            callback_rate,
+            time_in_force,
+            post_only,
+            reduce_only,
+            client_id,
+            tag,
+        } = req;
+        let code = code.as_str();
+
+        // 客户端订单id在未完成订单中唯一
+        if let Some(client_id) = client_id.as_ref() {
+            ensure!(!client_id.is_empty(), "client order id must not be empty");
+            ensure!(
+                !self.context.orders.contains_key(client_id),
+                "duplicate client order id: {client_id}"
+            );
+        }
+
+        if type_.is_limit() {
+            ensure!(price.is_some(), "limit order must have price");
+        } else {
+            ensure!(price.is_none(), "market order must not have price");
+        }
+
+        match type_ {
+            OrderType::Limit | OrderType::Market => {
+                ensure!(trigger_price.is_none(), "order must not have trigger price")
+            }
+            OrderType::TrailingStop => {
+                let callback_rate = callback_rate
+                    .ok_or_else(|| anyhow!("trailing stop order must have callback rate"))?;
+                ensure!(
+                    callback_rate > Decimal::ZERO && callback_rate < Decimal::ONE,
+                    "invalid callback rate: {callback_rate}"
+                );
+            }
+            _ => ensure!(
+                trigger_price.is_some_and(|p| p > Decimal::ZERO),
+                "trigger order must have trigger price"
+            ),
+        }
+
+        ensure!(
+            !post_only || type_.is_limit(),
+            "post only order must be limit order"
+        );
+
+        // 单向持仓: 按当前持仓确定方向, 有反向持仓时为平仓
+        let direction = if self.context.is_one_way() && self.context.positions.contains_key(code) {
+            self.context.net_direction(code, side)
+        } else {
+            direction
+        };
+
+        let mut size = size;
+        let reason = match self.context.symbols.get(code) {
+            None => Some(RejectReason::UnknownSymbol),
+            Some(symbol) => {
+                let actual_price = match type_ {
+                    OrderType::Market | OrderType::TrailingStop => symbol.price,
+                    OrderType::StopMarket | OrderType::TakeProfitMarket => trigger_price.unwrap(),
+                    _ => price.unwrap(),
+                };
+                ensure!(actual_price > Decimal::ZERO);
+                self.check_order(
+                    code,
+                    type_,
+                    direction,
+                    side,
+                    &mut size,
+                    price,
+                    trigger_price,
+                    actual_price,
+                    post_only,
+                    reduce_only,
+                )
+            }
+        };
+        if let Some(reason) = reason {
+            tracing::debug!("订单拒绝: {code} {direction} {side} 数量={size} 原因={reason}");
+        }
+
+        // 追踪止损单未指定激活价格时立即激活
+        let trail_price = if type_ == OrderType::TrailingStop && trigger_price.is_none() {
+            self.context.symbols.get(code).map(|symbol| symbol.price)
+        } else {
+            None
+        };
+
+        let order_id = client_id.unwrap_or_else(id_new);
+        let order = Order {
+            id: order_id.clone(),
+            code: code.to_string(),
+            type_,
+            direction,
+            side,
+            price,
+            size,
+            filled: Decimal::ZERO,
+            status: if reason.is_some() {
+                OrderStatus::Rejected
+            } else {
+                OrderStatus::New
+            },
+            time: self.curr_time,
+            active_time: self.curr_time + self.latency.submit_delay(),
+            cancel_time: None,
+            trigger_price,
+            callback_rate,
+            trail_price,
+            triggered: false,
+            time_in_force,
+            post_only,
+            reduce_only,
+            reason,
+            parent_id: None,
+            oco_id: None,
+            tag,
+        };
+
+        self.context.orders.insert(order_id.clone(), order);
+
+        Ok(order_id)
+    }
+
+    fn cancel_order(&mut self, id: &str) -> Result<()> {
+        let Some(order) = self.context.orders.get_mut(id) else {
+            return Ok(());
+        };
+
+        ensure!(matches!(
+            order.status,
+            OrderStatus::New
+                | OrderStatus::Pending
+                | OrderStatus::PartiallyFilled
+                | OrderStatus::Canceling
+                | OrderStatus::Waiting
+        ));
+
+        // 等待激活的订单未提交到交易所, 直接撤销
+        if order.status == OrderStatus::Waiting {
+            order.status = OrderStatus::Canceled;
+            return Ok(());
+        }
+
+        order.status = OrderStatus::Canceling;
+
+        // 重复撤单不改变撤单生效时间
+        if order.cancel_time.is_none() {
+            order.cancel_time = Some(self.curr_time + self.latency.cancel_delay());
+        }
+
+        Ok(())
+    }
+
+    fn amend_order(&mut self, id: &str, price: Decimal, size: Decimal) -> Result<()> {
+        let order = self
+            .context
+            .orders
+            .get(id)
+            .ok_or_else(|| anyhow!("order not found: {id}"))?;
+
+        ensure!(
+            matches!(
+                order.status,
+                OrderStatus::New | OrderStatus::Pending | OrderStatus::PartiallyFilled
+            ),
+            "order cannot be amended: {id} {}",
+            order.status
+        );
+        ensure!(
+            order.type_.is_limit(),
+            "only limit order can be amended: {id}"
+        );
+        ensure!(
+            size > order.filled,
+            "amend size must exceed filled size: {size}"
+        );
+        ensure!(price > Decimal::ZERO, "invalid price: {price}");
+
+        // 价格不变且数量减少时保留排队优先级
+        let keep_priority = order.price == Some(price) && size < order.size;
+
+        // 修改后的剩余数量按新订单规则检查, 检查时移除原订单, 不计入其冻结资金及冻结持仓
+        let (index, _, old) = self.context.orders.shift_remove_full(id).unwrap();
+        let mut remain = size - old.filled;
+        let reason = self.check_order(
+            &old.code,
+            old.type_,
+            old.direction,
+            old.side,
+            &mut remain,
+            Some(price),
+            old.trigger_price,
+            price,
+            old.post_only,
+            old.reduce_only,
+        );
+        if let Some(reason) = reason {
+            self.context.orders.shift_insert(index, id.to_string(), old);
+            bail!("order amend rejected: {id} {reason}");
+        }
+
+        let mut order = old;
+        order.price = Some(price);
+        order.size = order.filled + remain;
+        self.context
+            .orders
+            .shift_insert(index, id.to_string(), order);
+
+        // 失去优先级的订单按新订单重新排队: 按下单延迟生效, 首次撮合即成交时为吃单(只做挂单被拒绝)
+        if !keep_priority {
+            let mut order = self.context.orders.shift_remove(id).unwrap();
+            order.status = OrderStatus::New;
+            order.active_time = self.curr_time + self.latency.submit_delay();
+            self.context.orders.insert(id.to_string(), order);
+        }
+
+        Ok(())
+    }
+
+    fn place_orders(&mut self, reqs: Vec<OrderRequest>) -> Result<Vec<String>> {
+        let mut ids = Vec::with_capacity(reqs.len());
+        for req in reqs {
+            match self.submit_order(req) {
+                Ok(id) => ids.push(id),
+                Err(err) => {
+                    // 任一订单失败时撤回本批次已提交的订单
+                    for id in &ids {
+                        self.context.orders.shift_remove(id);
+                    }
+                    return Err(err);
+                }
+            }
+        }
+        Ok(ids)
+    }
+
+    fn place_oco(&mut self, first: OrderRequest, second: OrderRequest) -> Result<Vec<String>> {
+        ensure!(
+            first.code == second.code,
+            "oco orders must have the same code"
+        );
+        let ids = self.place_orders(vec![first, second])?;
+
+        // 任一订单被拒绝时撤销另一订单
+        let rejected = ids.iter().any(|id| {
+            self.context
+                .orders
+                .get(id)
+                .is_some_and(|order| order.status == OrderStatus::Rejected)
+        });
+        for (id, oco_id) in [(&ids[0], &ids[1]), (&ids[1], &ids[0])] {
+            let order = self.context.orders.get_mut(id).unwrap();
+            if rejected && order.status != OrderStatus::Rejected {
+                order.status = OrderStatus::Canceled;
+            }
+            order.oco_id = Some(oco_id.clone());
+        }
+
+        Ok(ids)
+    }
+
+    fn place_bracket(
+        &mut self,
+        entry: OrderRequest,
+        take_profit: Decimal,
+        stop_loss: Decimal,
+    ) -> Result<Vec<String>> {
+        ensure!(
+            !entry.reduce_only,
+            "bracket entry order must not be reduce only"
+        );
+        let symbol = self
+            .context
+            .symbols
+            .get(&entry.code)
+            .ok_or_else(|| anyhow!("symbol not found: {}", entry.code))?;
+
+        // 做多: 止损 < 入场价 < 止盈; 做空相反
+        let entry_price = entry.price.or(entry.trigger_price).unwrap_or(symbol.price);
+        let valid = match entry.side {
+            Side::Buy => stop_loss < entry_price && entry_price < take_profit,
+            Side::Sell => take_profit < entry_price && entry_price < stop_loss,
+        };
+        ensure!(
+            valid && stop_loss > Decimal::ZERO,
+            "invalid bracket prices: entry={entry_price} tp={take_profit} sl={stop_loss}"
+        );
+        ensure!(
+            symbol.is_tick_price(take_profit) && symbol.is_tick_price(stop_loss),
+            "invalid bracket tick: tp={take_profit} sl={stop_loss} tick={}",
+            symbol.price_tick
+        );
+
+        let entry_id = self.submit_order(entry)?;
+        let entry = self.context.orders.get(&entry_id).unwrap().clone();
+        if entry.status == OrderStatus::Rejected {
+            return Ok(vec![entry_id]);
+        }
+
+        let is_open = matches!(
+            (entry.direction, entry.side),
+            (Direction::Long, Side::Buy) | (Direction::Short, Side::Sell)
+        );
+        if !is_open {
+            self.context.orders.shift_remove(&entry_id);
+            bail!("bracket entry order must open position");
+        }
+
+        // 止盈止损单等待入场单成交后激活, 互为OCO
+        let close_side = match entry.side {
+            Side::Buy => Side::Sell,
+            Side::Sell => Side::Buy,
+        };
+        let tp_id = id_new();
+        let sl_id = id_new();
+        for (id, type_, trigger_price, oco_id) in [
+            (&tp_id, OrderType::TakeProfitMarket, take_profit, &sl_id),
+            (&sl_id, OrderType::StopMarket, stop_loss, &tp_id),
+        ] {
+            let order = Order {
+                id: id.clone(),
+                code: entry.code.clone(),
+                type_,
+                direction: entry.direction,
+                side: close_side,
+                price: None,
+                size: entry.size,
+                filled: Decimal::ZERO,
+                status: OrderStatus::Waiting,
+                time: self.curr_time,
+                active_time: self.curr_time,
+                cancel_time: None,
+                trigger_price: Some(trigger_price),
+                callback_rate: None,
+                trail_price: None,
+                triggered: false,
+                time_in_force: TimeInForce::Gtc,
+                post_only: false,
+                reduce_only: true,
+                reason: None,
+                parent_id: Some(entry_id.clone()),
+                oco_id: Some(oco_id.clone()),
+                tag: entry.tag.clone(),
+            };
+            self.context.orders.insert(id.clone(), order);
+        }
+
+        Ok(vec![entry_id, tp_id, sl_id])
+    }
+
+    fn cancel_orders(&mut self, ids: &[String]) -> Result<()> {
+        // 先检查全部订单均可撤销, 再统一撤单
+        for id in ids {
+            if let Some(order) = self.context.orders.get(id) {
+                ensure!(
+                    matches!(
+                        order.status,
+                        OrderStatus::New
+                            | OrderStatus::Pending
+                            | OrderStatus::PartiallyFilled
+                            | OrderStatus::Canceling
+                            | OrderStatus::Waiting
+                    ),
+                    "order cannot be canceled: {id} {}",
+                    order.status
+                );
+            }
+        }
+        for id in ids {
+            self.cancel_order(id)?;
+        }
+        Ok(())
+    }
+}
+
+impl Backtest {
+    #[tracing::instrument(name = "backtest", skip_all)]
+    pub fn run(&mut self) -> Result<Report> {
+        let codes = self
+            .context
+            .symbols
+            .keys()
+            .cloned()
+            .collect::<Vec<String>>();
+
+        self.update_bar_prices()?;
+        for code in &codes {
+            // 丢弃开始时间之前的资金费率, 仅保留最新值
+            if let Some((_, rate)) = self.due_funding_rates(code, self.curr_time)?.pop() {
+                let symbol = self.context.symbols.get_mut(code).unwrap();
+                symbol.funding_rate = rate;
+            }
+        }
+
+        self.history_equities
+            .push(self.context.calc_equity().to_f64().unwrap_or(0.0));
+
+        unsafe {
+            let this = self as *mut Backtest;
+            self.strategy.on_start(&mut *this)?;
         }
 
         for code in &codes {
@@ -481,18 +1895,15 @@ impl Backtest {
             );
             let _guard = span.enter();
 
-            for code in &codes {
-                let bars_df = self.context.bars.get(code).unwrap();
-                let open_price = Decimal::try_from(df_f64(bars_df, "open", self.bar_idx)?)?;
-                let symbol = self.context.symbols.get_mut(code).unwrap();
-                symbol.mark_price = open_price;
-                symbol.price = open_price;
-            }
+            self.update_bar_prices()?;
 
-            self.cross_order()?;
+            let last_fills = self.cross_order()?;
+            self.check_liquidation(&last_fills)?;
 
             self.bar_idx += 1;
-            self.curr_time += Duration::minutes(1);
+            self.curr_time += self.interval;
+
+            self.settle_funding()?;
 
             self.history_equities
                 .push(self.context.calc_equity().to_f64().unwrap_or(0.0));
@@ -502,10 +1913,11 @@ impl Backtest {
                 self.strategy.on_signal(&mut *this)?;
             }
 
+            let sec = self.curr_time.second();
             let min = self.curr_time.minute();
             let hour = self.curr_time.hour();
 
-            // 每秒事件（回测中每分钟触发一次）
+            // 每秒事件（回测中每根K线触发一次）
             unsafe {
                 let this = self as *mut Backtest;
                 self.strategy
@@ -513,13 +1925,15 @@ impl Backtest {
             }
 
             // 每分钟事件
-            unsafe {
-                let this = self as *mut Backtest;
-                self.strategy
-                    .on_timer(&mut *this, Timer::Minutely, self.curr_time)?;
+            if sec == 0 {
+                unsafe {
+                    let this = self as *mut Backtest;
+                    self.strategy
+                        .on_timer(&mut *this, Timer::Minutely, self.curr_time)?;
+                }
             }
 
-            if min == 0 {
+            if sec == 0 && min == 0 {
                 unsafe {
                     let this = self as *mut Backtest;
                     self.strategy
@@ -527,7 +1941,7 @@ impl Backtest {
                 }
             }
 
-            if min == 0 && hour == 0 {
+            if sec == 0 && min == 0 && hour == 0 {
                 unsafe {
                     let this = self as *mut Backtest;
                     self.strategy
@@ -547,40 +1961,170 @@ impl Backtest {
             &self.history_equities,
             self.start_time,
             self.end_time,
+            self.interval,
             &self.trades,
+            &self.fundings,
         )
     }
 }
 
-mod bars {
+pub(crate) mod bars {
     use crate::{helpers::*, types::*};
     use anyhow::{Result, bail, ensure};
-    use chrono::Duration;
     use polars::prelude::*;
-    use std::path::PathBuf;
+
+    /// 基础K线周期(历史数据文件的周期)
+    const BASE_INTERVAL: &str = "1m";
+
+    /// 将K线重采样为指定周期(窗口左闭右开, 以窗口开始时间标记)
+    pub fn resample(df: LazyFrame, interval: &str) -> Result<LazyFrame> {
+        let every = polars::time::Duration::try_parse(interval)?;
+        let options = DynamicGroupOptions {
+            every,
+            period: every,
+            offset: polars::time::Duration::try_parse("0s")?,
+            label: Label::Left,
+            closed_window: ClosedWindow::Left,
+            start_by: StartBy::WindowBound,
+            ..Default::default()
+        };
+
+        Ok(df.group_by_dynamic(col("time"), [], options).agg([
+            col("open").first(),
+            col("high").max(),
+            col("low").min(),
+            col("close").last(),
+            col("size").sum(),
+            col("cash").sum(),
+            col("trades").sum(),
+            col("taker_size").sum(),
+            col("taker_cash").sum(),
+        ]))
+    }
+
+    /// 加载秒级K线, 数据文件不存在时返回 None
+    #[tracing::instrument(skip_all)]
+    pub fn load_sub_bars(
+        data_dir: &str,
+        market: Market,
+        code: &str,
+        start_time: Time,
+        end_time: Time,
+    ) -> Result<Option<SubBars>> {
+        let data_path = market
+            .dir(data_dir)
+            .join("bars")
+            .join(format!("{code}-1s.data"));
+        if !data_path.exists() {
+            return Ok(None);
+        }
+
+        let file = std::fs::File::open(&data_path)?;
+        let df = IpcReader::new(file)
+            .finish()?
+            .lazy()
+            .select([
+                col("time").cast(DataType::Int64),
+                col("open").cast(DataType::Float64),
+                col("high").cast(DataType::Float64),
+                col("low").cast(DataType::Float64),
+                col("close").cast(DataType::Float64),
+            ])
+            .filter(
+                col("time")
+                    .gt_eq(lit(start_time.timestamp_millis()))
+                    .and(col("time").lt(lit(end_time.timestamp_millis()))),
+            )
+            .drop_nulls(None)
+            .sort(["time"], Default::default())
+            .collect()?;
+
+        let times = df.column("time")?.i64()?.into_no_null_iter().collect();
+        let prices = df
+            .column("open")?
+            .f64()?
+            .into_no_null_iter()
+            .zip(df.column("high")?.f64()?.into_no_null_iter())
+            .zip(df.column("low")?.f64()?.into_no_null_iter())
+            .zip(df.column("close")?.f64()?.into_no_null_iter())
+            .map(|(((open, high), low), close)| [open, high, low, close])
+            .collect();
+
+        Ok(Some(SubBars { times, prices }))
+    }
 
     #[tracing::instrument(skip_all)]
     pub fn load(
         data_dir: &str,
+        market: Market,
         code: &str,
         start_time: Time,
         end_time: Time,
+        interval: &str,
         history_bar_len: usize,
     ) -> Result<DataFrame> {
-        let data_path = PathBuf::from(data_dir)
-            .join("bars")
-            .join(format!("{code}.data"));
+        // 优先使用对应周期的数据文件, 否则由基础周期数据重采样
+        let bars_dir = market.dir(data_dir).join("bars");
+        let interval_path = bars_dir.join(format!("{code}-{interval}.data"));
+        let is_base = interval_from_str(interval)? == interval_from_str(BASE_INTERVAL)?;
+        let (data_path, need_resample) = if !is_base && interval_path.exists() {
+            (interval_path, false)
+        } else {
+            (
+                bars_dir.join(format!("{code}.data")),
+                need_resample(code, BASE_INTERVAL, interval)?,
+            )
+        };
 
         ensure!(data_path.exists());
 
-        let start_naive = start_time.naive_utc() - Duration::minutes(history_bar_len as i64);
+        let file = std::fs::File::open(&data_path)?;
+        let data_df = IpcReader::new(file).finish()?.lazy();
+        align(
+            code,
+            data_df,
+            need_resample,
+            start_time,
+            end_time,
+            interval,
+            history_bar_len,
+        )
+    }
+
+    /// 数据周期与回测周期不同时是否需要重采样, 回测周期不是数据周期的整数倍时报错
+    pub fn need_resample(code: &str, data_interval: &str, interval: &str) -> Result<bool> {
+        let interval_dur = interval_from_str(interval)?;
+        let data_dur = interval_from_str(data_interval)?;
+        if interval_dur == data_dur {
+            return Ok(false);
+        }
+
+        ensure!(
+            interval_dur > data_dur && interval_dur.num_seconds() % data_dur.num_seconds() == 0,
+            "缺失数据: 交易对={code}, 周期={interval}"
+        );
+        Ok(true)
+    }
+
+    /// 将K线对齐到回测时间序列(开始时间前 history_bar_len 根至结束时间), 存在缺失K线时报错
+    pub fn align(
+        code: &str,
+        data_df: LazyFrame,
+        need_resample: bool,
+        start_time: Time,
+        end_time: Time,
+        interval: &str,
+        history_bar_len: usize,
+    ) -> Result<DataFrame> {
+        let interval_dur = interval_from_str(interval)?;
+        let start_naive = start_time.naive_utc() - interval_dur * history_bar_len as i32;
         let end_naive = end_time.naive_utc();
 
         let time_series = polars::time::date_range(
             "time".into(),
             start_naive,
             end_naive,
-            polars::time::Duration::parse("1m"),
+            polars::time::Duration::try_parse(interval)?,
             ClosedWindow::Both,
             TimeUnit::Milliseconds,
             Some(&TIME_TZ),
@@ -589,13 +2133,16 @@ mod bars {
 
         let time_df = DataFrame::new(vec![time_series])?;
 
-        let file = std::fs::File::open(&data_path)?;
-        let data_df = IpcReader::new(file).finish()?;
+        let data_df = if need_resample {
+            resample(data_df, interval)?
+        } else {
+            data_df
+        };
 
         let mut result = time_df
             .lazy()
             .join(
-                data_df.lazy(),
+                data_df,
                 [col("time")],
                 [col("time")],
                 JoinArgs::new(JoinType::Left),
@@ -644,141 +2191,798 @@ mod bars {
     }
 }
 
+pub(crate) mod funding {
+    use crate::{helpers::*, types::*};
+    use anyhow::Result;
+    use polars::prelude::*;
+    use rust_decimal::prelude::*;
+    use std::collections::VecDeque;
+
+    #[tracing::instrument(skip_all)]
+    pub fn load(
+        data_dir: &str,
+        market: Market,
+        code: &str,
+        end_time: Time,
+    ) -> Result<Option<VecDeque<(Time, Decimal)>>> {
+        let data_path = market
+            .dir(data_dir)
+            .join("funding")
+            .join(format!("{code}.data"));
+
+        if !data_path.exists() {
+            tracing::trace!("资金费率数据不存在: {code}");
+            return Ok(None);
+        }
+
+        let file = std::fs::File::open(&data_path)?;
+        let df = IpcReader::new(file).finish()?;
+
+        let time_col = df.column("time")?.cast(&DataType::Int64)?;
+        let time_col = time_col.i64()?;
+        let rate_col = df.column("rate")?.f64()?;
+
+        let mut rates = VecDeque::with_capacity(df.height());
+        for (ms, rate) in time_col.into_iter().zip(rate_col.into_iter()) {
+            let (Some(ms), Some(rate)) = (ms, rate) else {
+                continue;
+            };
+            let time = time_from_millis(ms)?;
+            if time > end_time {
+                break;
+            }
+            rates.push_back((time, Decimal::try_from(rate)?));
+        }
+
+        Ok(Some(rates))
+    }
+}
+
 pub mod history {
-    use crate::helpers::*;
+    use crate::{
+        helpers::*,
+        symbols::SymbolStore,
+        types::{Market, Time},
+    };
     use ::zip::ZipArchive;
-    use anyhow::{Result, anyhow};
-    use chrono::{Datelike, TimeZone};
+    use anyhow::{Context, Result, anyhow, ensure};
+    use chrono::{Datelike, DurationRound, NaiveDate, NaiveTime, TimeZone};
     use polars::prelude::*;
     use reqwest::Client;
-    use std::{io::Cursor, path::PathBuf};
+    use serde::Serialize;
+    use sha2::{Digest, Sha256};
+    use std::{
+        io::{Cursor, Write},
+        path::{Path, PathBuf},
+        str::FromStr,
+        sync::Mutex,
+    };
     use tokio::{
-        task::spawn_blocking,
+        sync::Semaphore,
+        task::{JoinSet, spawn_blocking},
         time::{Duration, sleep},
     };
+    use tracing::Instrument;
 
+    /// 币安历史数据最早月份
     const START_YEAR: i32 = 2020;
     const START_MONTH: u32 = 1;
+    /// 下载最大尝试次数
+    const MAX_ATTEMPTS: u32 = 5;
+    /// 首次重试间隔, 之后每次翻倍
+    const RETRY_DELAY: Duration = Duration::from_millis(500);
+    /// 最大重试间隔
+    const RETRY_MAX_DELAY: Duration = Duration::from_secs(30);
+    /// K线数据间隔(1分钟)
+    const BAR_INTERVAL: i64 = 60_000;
+    /// K线数据覆盖判断误差(1分钟)
+    const BAR_SLACK: i64 = 60_000;
+    /// 资金费率最长结算间隔(8小时)
+    const FUNDING_INTERVAL: i64 = 8 * 3_600_000;
+    /// 资金费率覆盖判断误差(最长结算间隔8小时)
+    const FUNDING_SLACK: i64 = 8 * 3_600_000;
+
+    impl Market {
+        /// 交易所信息接口
+        fn exchange_info_url(&self) -> &'static str {
+            match self {
+                Market::Um => "https://fapi.binance.com/fapi/v1/exchangeInfo",
+                Market::Cm => "https://dapi.binance.com/dapi/v1/exchangeInfo",
+            }
+        }
+
+        /// 归档下载地址, 如: monthly + klines/BTCUSDT/1m/BTCUSDT-1m-2020-01.zip
+        fn archive_url(&self, period: &str, path: &str) -> String {
+            format!("https://data.binance.vision/data/futures/{self}/{period}/{path}")
+        }
+    }
 
+    /// 同步进度
+    #[derive(Debug, Clone, Default, Serialize)]
+    pub struct SyncProgress {
+        /// 已下载字节数
+        pub bytes: u64,
+        /// 已完成文件数(含已缓存及交易所未发布的文件)
+        pub files_done: usize,
+        /// 文件总数(月度归档缺失时追加日度归档)
+        pub files_total: usize,
+    }
+
+    /// 同步上下文: 各交易对及各月份并发同步时共享
+    #[derive(Clone)]
+    struct Syncer {
+        /// HTTP 客户端
+        client: Client,
+        /// 并发数限制(下载及解压)
+        semaphore: Arc<Semaphore>,
+        /// 数据目录
+        data_dir: String,
+        /// 市场数据目录: {data_dir}/{market}
+        dir: PathBuf,
+        /// 市场
+        market: Market,
+        /// 当前月份(UTC)
+        cur_month: (i32, u32),
+        /// K线同步截止时间(不含当日)
+        end: i64,
+        /// 资金费率同步截止时间(不含当月)
+        month_end: i64,
+        /// 同步进度
+        progress: Arc<Mutex<SyncProgress>>,
+        /// 进度回调
+        on_progress: Arc<dyn Fn(&SyncProgress) + Send + Sync>,
+        /// 失败记录
+        failures: Arc<Mutex<Vec<String>>>,
+    }
+
+    /// 同步回测所需的K线数据: 回测区间及其前 history_bar_len 根K线
+    ///
+    /// 各交易对及各月份的归档最多 concurrency 个同时下载(解压在阻塞线程池中进行), 每下载一块数据或完成一个文件时回调 on_progress
     #[tracing::instrument(skip_all)]
     #[tokio::main(flavor = "current_thread")]
-    pub async fn sync_bars(dir: &str, codes: &[String]) -> Result<()> {
-        sync(dir, codes).await
+    pub async fn sync_bars(
+        dir: &str,
+        codes: &[String],
+        market: &str,
+        start_time: Time,
+        end_time: Time,
+        interval: &str,
+        history_bar_len: usize,
+        concurrency: usize,
+        on_progress: impl Fn(&SyncProgress) + Send + Sync + 'static,
+    ) -> Result<()> {
+        ensure!(concurrency > 0, "并发数必须大于0");
+        let market = Market::from_str(market)?;
+        market.migrate_legacy_dir(dir)?;
+        let interval = interval_from_str(interval)?;
+        let start_time = start_time.duration_trunc(interval)? - interval * history_bar_len as i32;
+        let end_time = end_time.duration_trunc(interval)? + interval;
+
+        // 月度归档仅包含已结束的月份, 日度归档仅包含已结束的日期
+        let now = chrono::Utc::now();
+        let cur_month = (now.year(), now.month());
+        let end = end_time.timestamp_millis();
+        let syncer = Syncer {
+            client: Client::builder()
+                .timeout(std::time::Duration::from_secs(30))
+                .build()?,
+            semaphore: Arc::new(Semaphore::new(concurrency)),
+            data_dir: dir.to_string(),
+            dir: market.dir(dir),
+            market,
+            cur_month,
+            end: end.min(day_start(now.date_naive())),
+            month_end: end.min(month_start(cur_month.0, cur_month.1)?),
+            progress: Default::default(),
+            on_progress: Arc::new(on_progress),
+            failures: Default::default(),
+        };
+
+        sync(syncer, codes, start_time.timestamp_millis()).await
+    }
+
+    /// 同步K线数据: 已有数据覆盖的部分不再下载, 全部覆盖时无需联网
+    #[tracing::instrument(skip_all)]
+    async fn sync(syncer: Syncer, codes: &[String], start: i64) -> Result<()> {
+        syncer.sync_symbols(codes).await?;
+        let symbol_store = SymbolStore::load(&syncer.data_dir, syncer.market)?;
+
+        // 最早从 START_YEAR-START_MONTH 开始
+        let start = start.max(month_start(START_YEAR, START_MONTH)?);
+
+        let mut tasks = JoinSet::new();
+        for code in codes {
+            // 上线前没有数据, 避免逐月探测
+            let start = symbol_store
+                .listing_time(code)
+                .map_or(start, |listing| start.max(listing));
+
+            let span = tracing::info_span!("", ________topic________ = format_args!("{code}"));
+            tasks.spawn(
+                syncer
+                    .clone()
+                    .sync_code(code.clone(), start)
+                    .instrument(span),
+            );
+        }
+        while let Some(result) = tasks.join_next().await {
+            result??;
+        }
+
+        let failures = syncer.failures.lock().unwrap();
+        ensure!(
+            failures.is_empty(),
+            "数据同步失败: {}个文件\n{}",
+            failures.len(),
+            failures.join("\n")
+        );
+
+        Ok(())
+    }
+
+    impl Syncer {
+        /// 增加文件总数
+        fn add_files(&self, count: usize) {
+            self.update(|progress| progress.files_total += count);
+        }
+
+        /// 更新进度并回调
+        fn update(&self, f: impl FnOnce(&mut SyncProgress)) {
+            let progress = {
+                let mut progress = self.progress.lock().unwrap();
+                f(&mut progress);
+                progress.clone()
+            };
+            (self.on_progress)(&progress);
+        }
+
+        /// 同步交易对的资金费率及K线数据, 有归档失败时记录并跳过合并
+        async fn sync_code(self, code: String, start: i64) -> Result<()> {
+            // 资金费率仅有月度归档
+            self.sync_funding_rates(&code, start).await?;
+
+            let feather_path = self.dir.join("bars").join(format!("{}.data", code));
+
+            let symbol = self.market.symbol(&code);
+            let resources_dir = self.dir.join("resources");
+            let segments = covered_segments(&feather_path, BAR_INTERVAL + BAR_SLACK)?;
+            let ranges = missing_ranges(&segments, start, self.end, BAR_SLACK);
+
+            // 已有数据中间缺失的月份, 补齐失败时继续使用已有数据
+            let mut hole_months = Vec::new();
+            for (hole_start, hole_end) in &ranges {
+                if *hole_start > start && *hole_end < self.end {
+                    hole_months.extend(months_between(*hole_start, *hole_end)?);
+                }
+            }
+
+            let mut months = Vec::new();
+            for (start, end) in &ranges {
+                months.extend(months_between(*start, *end)?);
+            }
+
+            // 同步区间内已结束但仍由日度归档补齐的月份, 月度归档发布后替换
+            let range_months = months_between(start, self.end)?;
+            let daily_files = daily_archives(&resources_dir, &format!("{symbol}-1m-"))?;
+            let mut replace_months = daily_files
+                .iter()
+                .map(|(date, _)| (date.year(), date.month()))
+                .filter(|month| {
+                    *month < self.cur_month
+                        && range_months.contains(month)
+                        && !months.contains(month)
+                })
+                .collect::<Vec<_>>();
+            replace_months.sort();
+            replace_months.dedup();
+            months.extend(replace_months.iter().copied());
+            months.sort();
+            months.dedup();
+
+            if months.is_empty() {
+                tracing::trace!("数据已覆盖回测区间");
+                return Ok(());
+            }
+
+            let mut days = Vec::new();
+            for (start, end) in &ranges {
+                days.extend(days_between(*start, *end)?);
+            }
+
+            let mut tasks = JoinSet::new();
+            for (year, month) in months {
+                let in_month = |date: &NaiveDate| (date.year(), date.month()) == (year, month);
+                let span = tracing::info_span!(
+                    "",
+                    ________topic________ = format_args!("{year:04}-{month:02}")
+                );
+                let fetch = self.clone().fetch_bar_month(
+                    symbol.clone(),
+                    (year, month),
+                    days.iter().copied().filter(in_month).collect(),
+                    daily_files
+                        .iter()
+                        .filter(|(date, _)| in_month(date))
+                        .map(|(_, path)| path.clone())
+                        .collect(),
+                );
+                tasks.spawn(async move { ((year, month), fetch.await) }.instrument(span));
+            }
+
+            let mut csv_paths = Vec::new();
+            let mut failed = false;
+            while let Some(result) = tasks.join_next().await {
+                match result? {
+                    (_, Ok(paths)) => csv_paths.extend(paths),
+                    // 仅替换日度数据的月份已有数据, 替换失败不影响回测
+                    (month, Err(e)) if replace_months.contains(&month) => {
+                        tracing::warn!("月度归档替换失败, 继续使用日度数据: {e:#}");
+                    }
+                    (month, Err(e)) if hole_months.contains(&month) => {
+                        tracing::warn!("缺失数据补齐失败, 继续使用已有数据: {e:#}");
+                    }
+                    (_, Err(e)) => {
+                        tracing::error!("同步失败: {e:#}");
+                        self.failures.lock().unwrap().push(format!("{e:#}"));
+                        failed = true;
+                    }
+                }
+            }
+            csv_paths.sort();
+
+            // 有归档失败时不合并, 避免中间缺失的数据被视为已覆盖
+            if !failed {
+                process_single_symbol(&csv_paths, &feather_path).await?;
+            }
+
+            Ok(())
+        }
+
+        /// 获取一个月的K线 CSV: 已结束的月份优先使用月度归档(并删除该月日度归档), 否则使用日度归档
+        async fn fetch_bar_month(
+            self,
+            symbol: String,
+            (year, month): (i32, u32),
+            days: Vec<NaiveDate>,
+            daily_files: Vec<PathBuf>,
+        ) -> Result<Vec<PathBuf>> {
+            let resources_dir = self.dir.join("resources");
+
+            // 币安格式: BTCUSDT-1m-2020-01.zip
+            if (year, month) < self.cur_month {
+                self.add_files(1);
+                let zip_filename = format!("{symbol}-1m-{year:04}-{month:02}.zip");
+                let url = self
+                    .market
+                    .archive_url("monthly", &format!("klines/{symbol}/1m/{zip_filename}"));
+                if let Some(csv_path) = self
+                    .fetch_archive(&url, &resources_dir.join(&zip_filename))
+                    .await?
+                {
+                    // 月度归档已覆盖该月, 删除日度归档
+                    for zip_path in &daily_files {
+                        remove_archive(zip_path)?;
+                    }
+                    return Ok(vec![csv_path]);
+                }
+            }
+
+            // 当月或月度归档尚未发布: 使用日度归档, 币安格式: BTCUSDT-1m-2020-01-01.zip
+            self.add_files(days.len());
+            let mut tasks = JoinSet::new();
+            for date in days {
+                let zip_filename = format!("{symbol}-1m-{}.zip", date.format("%Y-%m-%d"));
+                let url = self
+                    .market
+                    .archive_url("daily", &format!("klines/{symbol}/1m/{zip_filename}"));
+                let zip_path = resources_dir.join(&zip_filename);
+                let syncer = self.clone();
+                tasks.spawn(
+                    async move { syncer.fetch_archive(&url, &zip_path).await }.in_current_span(),
+                );
+            }
+
+            let mut csv_paths = Vec::new();
+            while let Some(result) = tasks.join_next().await {
+                csv_paths.extend(result??);
+            }
+
+            Ok(csv_paths)
+        }
+
+        /// 获取归档对应的 CSV, 交易所未发布时返回 None
+        async fn fetch_archive(&self, url: &str, zip_path: &Path) -> Result<Option<PathBuf>> {
+            let _permit = self.semaphore.acquire().await?;
+            let result = self
+                .try_fetch_archive(url, zip_path)
+                .await
+                .with_context(|| zip_path.display().to_string());
+            self.update(|progress| progress.files_done += 1);
+            result
+        }
+
+        /// 归档不存在时下载, 下载后解压; 已缓存的归档无法解压时删除后重新下载
+        async fn try_fetch_archive(&self, url: &str, zip_path: &Path) -> Result<Option<PathBuf>> {
+            if zip_path.exists() {
+                tracing::trace!("数据已存在: {}", zip_path.display());
+                let path = zip_path.to_path_buf();
+                match spawn_blocking(move || extract_zip_to_csv(&path)).await? {
+                    Ok(()) => return Ok(Some(zip_path.with_extension("csv"))),
+                    Err(e) => {
+                        tracing::warn!("缓存归档损坏, 重新下载: {}, {e:#}", zip_path.display());
+                        remove_archive(zip_path)?;
+                    }
+                }
+            }
+
+            tracing::trace!("下载数据: {url}");
+            if !self.download_zip_file(url, zip_path).await? {
+                return Ok(None);
+            }
+
+            let path = zip_path.to_path_buf();
+            spawn_blocking(move || extract_zip_to_csv(&path)).await??;
+            Ok(Some(zip_path.with_extension("csv")))
+        }
+    }
+
+    /// 列出资源目录下的日度归档, 如: BTCUSDT-1m-2020-01-01.zip
+    fn daily_archives(resources_dir: &Path, prefix: &str) -> Result<Vec<(NaiveDate, PathBuf)>> {
+        if !resources_dir.exists() {
+            return Ok(Vec::new());
+        }
+
+        let mut archives = Vec::new();
+        for entry in std::fs::read_dir(resources_dir)? {
+            let path = entry?.path();
+            let Some(name) = path.file_name().and_then(|name| name.to_str()) else {
+                continue;
+            };
+            let date = name
+                .strip_prefix(prefix)
+                .and_then(|name| name.strip_suffix(".zip"))
+                .and_then(|date| NaiveDate::parse_from_str(date, "%Y-%m-%d").ok());
+            if let Some(date) = date {
+                archives.push((date, path));
+            }
+        }
+        archives.sort();
+
+        Ok(archives)
+    }
+
+    /// 删除归档及解压的 CSV
+    fn remove_archive(zip_path: &Path) -> Result<()> {
+        for path in [zip_path.to_path_buf(), zip_path.with_extension("csv")] {
+            match std::fs::remove_file(&path) {
+                Err(e) if e.kind() != std::io::ErrorKind::NotFound => return Err(e.into()),
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    /// 读取数据文件已覆盖的时间段(毫秒), 相邻数据间隔超过 max_gap 时视为中间缺失, 文件不存在时为空
+    fn covered_segments(path: &Path, max_gap: i64) -> Result<Vec<(i64, i64)>> {
+        if !path.exists() {
+            return Ok(Vec::new());
+        }
+
+        let file = std::fs::File::open(path)?;
+        let df = IpcReader::new(file).finish()?;
+        let time = df.column("time")?.cast(&DataType::Int64)?;
+        let mut times = time.i64()?.into_iter().flatten().collect::<Vec<i64>>();
+        times.sort_unstable();
+
+        let mut segments: Vec<(i64, i64)> = Vec::new();
+        for time in times {
+            match segments.last_mut() {
+                Some((_, last)) if time - *last <= max_gap => *last = time,
+                _ => segments.push((time, time)),
+            }
+        }
+        Ok(segments)
+    }
+
+    /// 月份起始时间(UTC, 毫秒)
+    fn month_start(year: i32, month: u32) -> Result<i64> {
+        chrono::Utc
+            .with_ymd_and_hms(year, month, 1, 0, 0, 0)
+            .single()
+            .map(|time| time.timestamp_millis())
+            .ok_or_else(|| anyhow!("无效月份: {year}-{month:02}"))
+    }
+
+    /// 日期起始时间(UTC, 毫秒)
+    fn day_start(date: NaiveDate) -> i64 {
+        date.and_time(NaiveTime::MIN).and_utc().timestamp_millis()
+    }
+
+    /// [start, end) 范围涉及的日期(UTC)
+    fn days_between(start: i64, end: i64) -> Result<Vec<NaiveDate>> {
+        let mut days = Vec::new();
+        if start >= end {
+            return Ok(days);
+        }
+
+        let mut date = chrono::Utc
+            .timestamp_millis_opt(start)
+            .single()
+            .ok_or_else(|| anyhow!("无效时间: {start}"))?
+            .date_naive();
+        while day_start(date) < end {
+            days.push(date);
+            date = date.succ_opt().ok_or_else(|| anyhow!("无效日期: {date}"))?;
+        }
+
+        Ok(days)
+    }
+
+    /// [start, end) 范围涉及的月份(UTC)
+    fn months_between(start: i64, end: i64) -> Result<Vec<(i32, u32)>> {
+        let mut months = Vec::new();
+        if start >= end {
+            return Ok(months);
+        }
+
+        let time = chrono::Utc
+            .timestamp_millis_opt(start)
+            .single()
+            .ok_or_else(|| anyhow!("无效时间: {start}"))?;
+        let (mut year, mut month) = (time.year(), time.month());
+        while month_start(year, month)? < end {
+            months.push((year, month));
+            month += 1;
+            if month > 12 {
+                month = 1;
+                year += 1;
+            }
+        }
+
+        Ok(months)
+    }
+
+    /// 已覆盖时间段(升序)未覆盖 [start, end) 的全部区间(含中间缺失), 误差在 slack 内视为已覆盖
+    fn missing_ranges(
+        segments: &[(i64, i64)],
+        start: i64,
+        end: i64,
+        slack: i64,
+    ) -> Vec<(i64, i64)> {
+        let mut ranges = Vec::new();
+        // 已覆盖至此
+        let mut cursor = start;
+        for &(first, last) in segments {
+            if cursor >= end {
+                break;
+            }
+            if first > cursor + slack {
+                ranges.push((cursor, first.min(end)));
+            }
+            cursor = cursor.max(last + slack);
+        }
+        if cursor < end {
+            ranges.push((cursor, end));
+        }
+        ranges
     }
 
-    #[tracing::instrument(skip_all)]
-    async fn sync(dir: &str, codes: &[String]) -> Result<()> {
-        let client = Client::builder()
-            .timeout(std::time::Duration::from_secs(30))
-            .build()?;
+    /// 合并数据并原子写入: 时间重复时以新数据为准, 按时间排序后先写入临时文件再替换, 避免中断时损坏已有数据
+    fn merge_ipc(path: &Path, df: DataFrame) -> Result<()> {
+        let df = if path.exists() {
+            let file = std::fs::File::open(path)?;
+            IpcReader::new(file).finish()?.vstack(&df)?
+        } else {
+            df
+        };
+        let mut df = df
+            .unique_stable(Some(&["time".to_string()]), UniqueKeepStrategy::Last, None)?
+            .sort(["time"], SortMultipleOptions::default())?;
 
-        for code in codes {
-            let span = tracing::info_span!("", ________topic________ = format_args!("{code}"));
-            let _guard = span.enter();
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
 
-            let feather_path = PathBuf::from(dir)
-                .join("bars")
-                .join(format!("{}.data", code));
+        let tmp_path = path.with_extension("data.tmp");
+        let mut file = std::fs::File::create(&tmp_path)?;
+        IpcWriter::new(&mut file).finish(&mut df)?;
+        file.sync_all()?;
+        drop(file);
+        std::fs::rename(&tmp_path, path)?;
 
-            if feather_path.exists() {
-                tracing::trace!("数据已存在");
-                continue;
+        Ok(())
+    }
+
+    impl Syncer {
+        /// 同步交易对元数据(exchangeInfo), 已有元数据包含全部交易对时跳过
+        #[tracing::instrument(skip_all)]
+        async fn sync_symbols(&self, codes: &[String]) -> Result<()> {
+            let has_file = crate::symbols::SYMBOLS_FILES
+                .iter()
+                .any(|name| self.dir.join(name).exists());
+            if has_file
+                && let Ok(symbol_store) = SymbolStore::load(&self.data_dir, self.market)
+                && codes.iter().all(|code| symbol_store.contains(code))
+            {
+                return Ok(());
             }
 
-            let now_utc = chrono::Utc::now();
-            let now_tz = TIME_TZ.from_utc_datetime(&now_utc.naive_utc());
-            let cur_year = now_tz.year();
-            let cur_month = now_tz.month();
-            let mut year = START_YEAR;
-            let mut month = START_MONTH;
+            tracing::trace!("下载交易对元数据");
 
-            loop {
-                if year > cur_year || (year == cur_year && month >= cur_month) {
-                    break;
-                }
+            let content = self
+                .client
+                .get(self.market.exchange_info_url())
+                .send()
+                .await?
+                .error_for_status()?
+                .text()
+                .await?;
 
-                let year_month_str = format!("{:04}-{:02}", year, month);
+            std::fs::create_dir_all(&self.dir)?;
+            std::fs::write(self.dir.join(crate::symbols::SYMBOLS_FILES[0]), content)?;
 
-                let span = tracing::info_span!(
-                    "",
-                    ________topic________ = format_args!("{year_month_str}")
-                );
-                let _guard = span.enter();
+            Ok(())
+        }
 
-                // 币安格式: BTCUSDT-1m-2020-01.zip
-                let symbol = format!("{}USDT", code);
-                let zip_filename = format!("{}-1m-{}.zip", symbol, year_month_str);
-                let save_path = PathBuf::from(dir).join("resources").join(&zip_filename);
+        /// 同步资金费率: 已有数据覆盖的部分不再下载
+        #[tracing::instrument(skip_all)]
+        async fn sync_funding_rates(&self, code: &str, start: i64) -> Result<()> {
+            let data_path = self.dir.join("funding").join(format!("{}.data", code));
 
-                let url = format!(
-                    "https://data.binance.vision/data/futures/um/monthly/klines/{}/1m/{}",
-                    symbol, zip_filename
+            let segments = covered_segments(&data_path, FUNDING_INTERVAL + FUNDING_SLACK)?;
+            let symbol = self.market.symbol(code);
+
+            let mut months = Vec::new();
+            for (start, end) in missing_ranges(&segments, start, self.month_end, FUNDING_SLACK) {
+                months.extend(months_between(start, end)?);
+            }
+
+            self.add_files(months.len());
+            let mut tasks = JoinSet::new();
+            for (year, month) in months {
+                // 币安格式: BTCUSDT-fundingRate-2020-01.zip
+                let zip_filename = format!("{symbol}-fundingRate-{year:04}-{month:02}.zip");
+                let zip_path = self.dir.join("resources").join(&zip_filename);
+                let url = self
+                    .market
+                    .archive_url("monthly", &format!("fundingRate/{symbol}/{zip_filename}"));
+                let syncer = self.clone();
+                tasks.spawn(
+                    async move { syncer.fetch_archive(&url, &zip_path).await }.in_current_span(),
                 );
+            }
 
-                if save_path.exists() {
-                    month += 1;
-                    if month > 12 {
-                        month = 1;
-                        year += 1;
+            let mut csv_paths = Vec::new();
+            let mut failed = false;
+            while let Some(result) = tasks.join_next().await {
+                match result? {
+                    Ok(path) => csv_paths.extend(path),
+                    Err(e) => {
+                        tracing::error!("同步失败: {e:#}");
+                        self.failures.lock().unwrap().push(format!("{e:#}"));
+                        failed = true;
                     }
-                    tracing::trace!("数据已存在: {code}-{year}-{month:02}");
-                    continue;
-                }
-
-                if let Some(parent) = save_path.parent() {
-                    std::fs::create_dir_all(parent)?;
                 }
+            }
+            csv_paths.sort();
 
-                tracing::trace!("下载数据: {code}-{year}-{month:02}");
-                download_zip_file(&client, &url, &save_path).await?;
+            // 有归档失败时不合并, 避免中间缺失的数据被视为已覆盖
+            if csv_paths.is_empty() || failed {
+                return Ok(());
+            }
 
-                month += 1;
-                if month > 12 {
-                    month = 1;
-                    year += 1;
-                }
+            // 币安 CSV 字段顺序: calc_time, funding_interval_hours, last_funding_rate
+            let schema = Arc::new(Schema::from_iter(vec![
+                Field::new("calc_time".into(), DataType::String),
+                Field::new("funding_interval_hours".into(), DataType::Int64),
+                Field::new("last_funding_rate".into(), DataType::Float64),
+            ]));
+
+            let df = LazyCsvReader::new_paths(
+                csv_paths
+                    .iter()
+                    .map(|path| PlPath::new(path.to_string_lossy().as_ref()))
+                    .collect::<Vec<_>>()
+                    .into(),
+            )
+            .with_has_header(true)
+            .with_schema(Some(schema))
+            .with_null_values(Some(NullValues::AllColumns(vec!["".into(), "None".into()])))
+            .finish()?;
+
+            // 结算时间可能带有毫秒偏移, 按分钟对齐
+            let time = col("calc_time")
+                .cast(DataType::Int64)
+                .floor_div(lit(60_000i64))
+                * lit(60_000i64);
+            let df = df
+                .filter(col("calc_time").neq(lit("calc_time")))
+                .select([
+                    time.cast(DataType::Datetime(
+                        TimeUnit::Milliseconds,
+                        Some(polars::datatypes::TimeZone::from_chrono(&TIME_TZ)),
+                    ))
+                    .alias("time"),
+                    col("last_funding_rate").alias("rate"),
+                ])
+                .sort(["time"], SortMultipleOptions::default());
+
+            let df = spawn_blocking(move || df.collect()).await??;
+            if df.height() > 0 {
+                merge_ipc(&data_path, df)?;
             }
 
-            let mut zip_paths = Vec::new();
-            let mut year = START_YEAR;
-            let mut month = START_MONTH;
+            Ok(())
+        }
 
+        /// 下载归档并按 .CHECKSUM 文件校验 SHA-256, 失败时按指数退避重试
+        ///
+        /// 交易所未发布(404)时返回 false, 超过最大重试次数时返回最后一次的错误
+        #[tracing::instrument(skip_all)]
+        async fn download_zip_file(&self, url: &str, save_path: &Path) -> Result<bool> {
+            let mut delay = RETRY_DELAY;
+            let mut attempt = 1;
             loop {
-                if year > cur_year || (year == cur_year && month >= cur_month) {
-                    break;
+                match self.try_download_zip_file(url).await {
+                    Ok(Some(bytes)) => {
+                        write_atomic(save_path, &bytes)?;
+                        return Ok(true);
+                    }
+                    Ok(None) => return Ok(false),
+                    Err(e) if attempt >= MAX_ATTEMPTS => {
+                        return Err(e.context(format!("下载失败(已重试{attempt}次): {url}")));
+                    }
+                    Err(e) => {
+                        tracing::warn!(
+                            "下载失败, {}ms后重试({attempt}/{MAX_ATTEMPTS}): {url}, {e:#}",
+                            delay.as_millis()
+                        );
+                        sleep(delay).await;
+                        delay = (delay * 2).min(RETRY_MAX_DELAY);
+                        attempt += 1;
+                    }
                 }
+            }
+        }
 
-                let span = tracing::info_span!(
-                    "",
-                    ________topic________ = format_args!("{:04}-{:02}", year, month)
-                );
-                let _guard = span.enter();
-
-                let symbol = format!("{}USDT", code);
-                let zip_path = PathBuf::from(dir)
-                    .join("resources")
-                    .join(format!("{}-1m-{:04}-{:02}.zip", symbol, year, month));
+        /// 下载一次归档并校验, 交易所未发布(404)时返回 None
+        async fn try_download_zip_file(&self, url: &str) -> Result<Option<Vec<u8>>> {
+            let mut resp = self.client.get(url).send().await?;
+            if resp.status() == reqwest::StatusCode::NOT_FOUND {
+                return Ok(None);
+            }
+            resp = resp.error_for_status()?;
 
-                if zip_path.exists() {
-                    extract_zip_to_csv(&zip_path).await?;
-                    zip_paths.push(zip_path);
-                }
+            let mut bytes = Vec::new();
+            while let Some(chunk) = resp.chunk().await? {
+                bytes.extend_from_slice(&chunk);
+                self.update(|progress| progress.bytes += chunk.len() as u64);
+            }
 
-                month += 1;
-                if month > 12 {
-                    month = 1;
-                    year += 1;
-                }
+            // 币安格式: <sha256>  BTCUSDT-1m-2020-01.zip
+            let resp = self.client.get(format!("{url}.CHECKSUM")).send().await?;
+            if resp.status() == reqwest::StatusCode::NOT_FOUND {
+                tracing::warn!("缺少校验文件, 跳过校验: {url}");
+                return Ok(Some(bytes));
             }
+            let checksum = resp.error_for_status()?.text().await?;
+            let expected = checksum
+                .split_whitespace()
+                .next()
+                .ok_or_else(|| anyhow!("校验文件为空: {url}.CHECKSUM"))?;
+
+            let actual = format!("{:x}", Sha256::digest(&bytes));
+            ensure!(
+                actual.eq_ignore_ascii_case(expected),
+                "校验失败: 期望={expected}, 实际={actual}"
+            );
 
-            process_single_symbol(code, dir, &feather_path).await?;
+            Ok(Some(bytes))
         }
-
-        Ok(())
     }
 
-    #[tracing::instrument(skip_all)]
-    async fn extract_zip_to_csv(zip_path: &PathBuf) -> Result<()> {
+    /// 解压归档中的 CSV, 已解压时跳过
+    fn extract_zip_to_csv(zip_path: &Path) -> Result<()> {
         let csv_path = zip_path.with_extension("csv");
 
         if csv_path.exists() {
@@ -804,87 +3008,31 @@ pub mod history {
 
         let csv_data = csv_data.ok_or_else(|| anyhow!("解析csv失败: {}", csv_path.display()))?;
 
-        if let Some(parent) = csv_path.parent() {
-            std::fs::create_dir_all(parent)?;
-        }
-
-        std::fs::write(&csv_path, csv_data)?;
-
-        Ok(())
+        write_atomic(&csv_path, &csv_data)
     }
 
-    #[tracing::instrument(skip_all)]
-    async fn download_zip_file(client: &Client, url: &str, save_path: &PathBuf) -> Result<()> {
-        loop {
-            let response = client.get(url).send().await;
-
-            match response {
-                Ok(resp) => {
-                    let status = resp.status();
-
-                    if status == reqwest::StatusCode::NOT_FOUND {
-                        return Ok(());
-                    }
-
-                    if !status.is_success() {
-                        sleep(Duration::from_millis(500)).await;
-                        continue;
-                    }
+    /// 原子写入文件: 先写入临时文件再替换, 中断时不会留下不完整的文件
+    fn write_atomic(path: &Path, data: &[u8]) -> Result<()> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
 
-                    let bytes = match resp.bytes().await {
-                        Ok(b) => b,
-                        Err(_) => {
-                            sleep(Duration::from_millis(500)).await;
-                            continue;
-                        }
-                    };
+        let mut tmp_name = path.file_name().unwrap_or_default().to_os_string();
+        tmp_name.push(".tmp");
+        let tmp_path = path.with_file_name(tmp_name);
 
-                    if std::fs::write(save_path, bytes).is_err() {
-                        sleep(Duration::from_millis(500)).await;
-                        continue;
-                    }
+        let mut file = std::fs::File::create(&tmp_path)?;
+        file.write_all(data)?;
+        file.sync_all()?;
+        drop(file);
+        std::fs::rename(&tmp_path, path)?;
 
-                    return Ok(());
-                }
-                Err(_) => {
-                    sleep(Duration::from_millis(500)).await;
-                    continue;
-                }
-            }
-        }
+        Ok(())
     }
 
+    /// 合并K线 CSV 到数据文件
     #[tracing::instrument(skip_all)]
-    async fn process_single_symbol(code: &str, dir: &str, feather_path: &PathBuf) -> Result<()> {
-        let now_utc = chrono::Utc::now();
-        let now_tz = TIME_TZ.from_utc_datetime(&now_utc.naive_utc());
-        let cur_year = now_tz.year();
-        let cur_month = now_tz.month();
-        let mut csv_paths = Vec::new();
-        let mut year = START_YEAR;
-        let mut month = START_MONTH;
-
-        loop {
-            if year > cur_year || (year == cur_year && month >= cur_month) {
-                break;
-            }
-
-            let symbol = format!("{}USDT", code);
-            let csv_path = PathBuf::from(dir)
-                .join("resources")
-                .join(format!("{}-1m-{:04}-{:02}.csv", symbol, year, month));
-
-            if csv_path.exists() {
-                csv_paths.push(csv_path);
-            }
-
-            month += 1;
-            if month > 12 {
-                month = 1;
-                year += 1;
-            }
-        }
-
+    async fn process_single_symbol(csv_paths: &[PathBuf], feather_path: &Path) -> Result<()> {
         if csv_paths.is_empty() {
             return Ok(());
         }
@@ -956,15 +3104,1073 @@ pub mod history {
         let df = df.sort(["time"], SortMultipleOptions::default());
 
         let df = spawn_blocking(move || df.collect()).await??;
+        if df.height() > 0 {
+            merge_ipc(feather_path, df)?;
+        }
 
-        if let Some(parent) = feather_path.parent() {
-            std::fs::create_dir_all(parent)?;
+        Ok(())
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        #[test]
+        fn test_missing_ranges() {
+            // 无数据时整个区间缺失
+            assert_eq!(missing_ranges(&[], 0, 1000, 10), vec![(0, 1000)]);
+
+            // 两端均缺失: 开头缺 [start, first), 结尾从最后一条数据加误差开始
+            assert_eq!(
+                missing_ranges(&[(100, 500)], 0, 1000, 10),
+                vec![(0, 100), (510, 1000)]
+            );
+
+            // 仅开头或仅结尾缺失
+            assert_eq!(missing_ranges(&[(100, 995)], 0, 1000, 10), vec![(0, 100)]);
+            assert_eq!(missing_ranges(&[(5, 500)], 0, 1000, 10), vec![(510, 1000)]);
+
+            // 误差范围内视为已覆盖
+            assert!(missing_ranges(&[(10, 990)], 0, 1000, 10).is_empty());
+
+            // 中间缺失及区间外的数据
+            assert_eq!(
+                missing_ranges(
+                    &[(-500, -100), (0, 400), (600, 1000), (2000, 3000)],
+                    0,
+                    1000,
+                    10
+                ),
+                vec![(410, 600)]
+            );
+        }
+
+        #[test]
+        fn test_covered_segments() {
+            let path = std::env::temp_dir()
+                .join(format!("fuxi-quant-segments-{}.data", std::process::id()));
+            assert!(covered_segments(&path, 20).unwrap().is_empty());
+
+            // 时间列中间缺失 [410, 600) 的数据
+            let times = (0..=40)
+                .chain(60..=100)
+                .map(|i| i * 10)
+                .collect::<Vec<i64>>();
+            let df = DataFrame::new(vec![Column::new("time".into(), times)]).unwrap();
+            merge_ipc(&path, df).unwrap();
+
+            let segments = covered_segments(&path, 20).unwrap();
+            assert_eq!(segments, vec![(0, 400), (600, 1000)]);
+            assert_eq!(missing_ranges(&segments, 0, 1000, 10), vec![(410, 600)]);
+
+            std::fs::remove_file(&path).unwrap();
         }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        slippage::FixedSlippage,
+        source::{ColumnMapping, DataFrameSource},
+    };
+    use polars::prelude::df;
 
-        let mut file = std::fs::File::create(feather_path)?;
+    /// 回测开始时间(毫秒)
+    const START_MS: i64 = 1_704_067_200_000;
 
-        IpcWriter::new(&mut file).finish(&mut df.clone())?;
+    /// 空策略
+    struct Noop;
 
-        Ok(())
+    impl Strategy for Noop {
+        fn on_start(&mut self, _: &mut dyn Engine) -> Result<()> {
+            Ok(())
+        }
+
+        fn on_stop(&mut self, _: &mut dyn Engine) -> Result<()> {
+            Ok(())
+        }
+
+        fn on_bar(&mut self, _: &mut dyn Engine, _: &str) -> Result<()> {
+            Ok(())
+        }
+
+        fn on_signal(&mut self, _: &mut dyn Engine) -> Result<()> {
+            Ok(())
+        }
+
+        fn on_timer(&mut self, _: &mut dyn Engine, _: Timer, _: Time) -> Result<()> {
+            Ok(())
+        }
+
+        fn on_order(&mut self, _: &mut dyn Engine, _: &str) -> Result<()> {
+            Ok(())
+        }
+
+        fn on_position(&mut self, _: &mut dyn Engine, _: &str) -> Result<()> {
+            Ok(())
+        }
+
+        fn on_liquidation(&mut self, _: &mut dyn Engine, _: &str, _: Direction) -> Result<()> {
+            Ok(())
+        }
+
+        fn on_slippage(
+            &mut self,
+            _: &mut dyn Engine,
+            _: &Order,
+            _: Decimal,
+            _: Decimal,
+        ) -> Result<Option<Decimal>> {
+            Ok(None)
+        }
+    }
+
+    /// BTC 1分钟K线回测: 每根K线为 [开, 高, 低, 收, 成交量], 资金 10000, 无手续费和滑点
+    fn backtest(bars: &[[f64; 5]]) -> Backtest {
+        let len = bars.len() as i64;
+        let column = |idx: usize| bars.iter().map(|bar| bar[idx]).collect::<Vec<f64>>();
+        let df = df!(
+            "time" => (0..len).map(|i| START_MS + i * 60_000).collect::<Vec<i64>>(),
+            "open" => column(0),
+            "high" => column(1),
+            "low" => column(2),
+            "close" => column(3),
+            "size" => column(4),
+        )
+        .unwrap();
+
+        let mut source = DataFrameSource::new("1m", ColumnMapping::default()).unwrap();
+        source.insert("BTC", df);
+        source.metadata.insert_symbol(Symbol::new("BTC"));
+
+        let mut bt = Backtest::new(
+            Box::new(Noop),
+            &["BTC".to_string()],
+            time_from_millis(START_MS).unwrap(),
+            time_from_millis(START_MS + (len - 1) * 60_000).unwrap(),
+            "1m",
+            dec!(10000),
+            0,
+            FeeSchedule::new(Decimal::ZERO, Decimal::ZERO).unwrap(),
+            Box::new(FixedSlippage::new(Decimal::ZERO).unwrap()),
+            Decimal::ZERO,
+            Decimal::ZERO,
+            "ohlc",
+            false,
+            Latency::default(),
+            "hedge",
+            1000,
+            &source,
+        )
+        .unwrap();
+        bt.update_bar_prices().unwrap();
+        bt
+    }
+
+    /// 撮合当前K线并进入下一根K线(同 run 主循环, 不回调策略)
+    fn step(bt: &mut Backtest) {
+        bt.update_bar_prices().unwrap();
+        let last_fills = bt.cross_order().unwrap();
+        bt.check_liquidation(&last_fills).unwrap();
+        bt.bar_idx += 1;
+        bt.curr_time += bt.interval;
+        bt.settle_funding().unwrap();
+    }
+
+    /// BTC 限价单请求
+    fn limit(direction: Direction, side: Side, size: Decimal, price: Decimal) -> OrderRequest {
+        OrderRequest::new("BTC", OrderType::Limit, direction, side, size, Some(price))
+    }
+
+    /// 订单状态及已成交数量(含历史订单)
+    fn order_state(bt: &Backtest, id: &str) -> (OrderStatus, Decimal) {
+        let order = bt.context.find_order(id).unwrap();
+        (order.status, order.filled)
+    }
+
+    /// 条件单(只填写撮合相关字段)
+    fn trigger(type_: OrderType, side: Side, trigger_price: Decimal) -> Order {
+        let time = time_from_millis(0).unwrap();
+        Order {
+            id: "1".into(),
+            code: "BTC".into(),
+            type_,
+            direction: Direction::Long,
+            side,
+            price: None,
+            size: dec!(1),
+            filled: Decimal::ZERO,
+            status: OrderStatus::Pending,
+            time,
+            active_time: time,
+            cancel_time: None,
+            trigger_price: Some(trigger_price),
+            callback_rate: None,
+            trail_price: None,
+            triggered: false,
+            time_in_force: TimeInForce::Gtc,
+            post_only: false,
+            reduce_only: false,
+            reason: None,
+            parent_id: None,
+            oco_id: None,
+            tag: None,
+        }
+    }
+
+    #[test]
+    fn test_price_path() {
+        // 开盘价距最低价(5)比距最高价(10)近
+        let (open, high, low, close) = (dec!(100), dec!(110), dec!(95), dec!(105));
+        let high_first = [open, high, low, close];
+        let low_first = [open, low, high, close];
+
+        let path = |mode, net_size| price_path(mode, net_size, open, high, low, close);
+        assert_eq!(path(IntrabarPath::Ohlc, dec!(0)), high_first);
+        assert_eq!(path(IntrabarPath::Olhc, dec!(0)), low_first);
+        assert_eq!(path(IntrabarPath::Nearest, dec!(0)), low_first);
+        assert_eq!(path(IntrabarPath::Worst, dec!(1)), low_first);
+        assert_eq!(path(IntrabarPath::Worst, dec!(-1)), high_first);
+        assert_eq!(path(IntrabarPath::Worst, dec!(0)), low_first);
+
+        // 开盘价距最高价(3)比距最低价(5)近
+        let path = price_path(IntrabarPath::Nearest, dec!(0), open, dec!(103), low, close);
+        assert_eq!(path, [open, dec!(103), low, close]);
+    }
+
+    #[test]
+    fn test_path_touch() {
+        let path = [dec!(100), dec!(110), dec!(95), dec!(105)];
+        let start = Touch::at(&path, Decimal::ZERO);
+
+        // 100 -> 110 线段中点到达 105
+        let touch = path_touch(&path, start, dec!(105), false).unwrap();
+        assert_eq!((touch.pos, touch.price), (dec!(0.5), dec!(105)));
+
+        // 110 -> 95 线段 12/15 处到达 98
+        let touch = path_touch(&path, start, dec!(98), true).unwrap();
+        assert_eq!((touch.pos, touch.price), (dec!(1.8), dec!(98)));
+
+        // 起点已到达时在起点成交
+        let touch = path_touch(&path, start, dec!(100), true).unwrap();
+        assert_eq!((touch.pos, touch.price), (dec!(0), dec!(100)));
+
+        assert!(path_touch(&path, start, dec!(90), true).is_none());
+
+        // 从第二段中点(102.5)开始, 之后不再到达 110
+        let start = Touch::at(&path, dec!(1.5));
+        assert_eq!(start.price, dec!(102.5));
+        assert!(path_touch(&path, start, dec!(110), false).is_none());
+    }
+
+    #[test]
+    fn test_trigger_order() {
+        let path = [dec!(100), dec!(110), dec!(95), dec!(105)];
+        let start = Touch::at(&path, Decimal::ZERO);
+
+        // 卖出止损: 价格下跌到 98 触发
+        let mut order = trigger(OrderType::StopMarket, Side::Sell, dec!(98));
+        let touch = trigger_order(&mut order, &path, start).unwrap();
+        assert_eq!((touch.pos, touch.price), (dec!(1.8), dec!(98)));
+
+        // 卖出止盈: 价格上涨到 108 触发
+        let mut order = trigger(OrderType::TakeProfitMarket, Side::Sell, dec!(108));
+        let touch = trigger_order(&mut order, &path, start).unwrap();
+        assert_eq!((touch.pos, touch.price), (dec!(0.8), dec!(108)));
+
+        // 买入止损: 价格未上涨到 120
+        let mut order = trigger(OrderType::StopMarket, Side::Buy, dec!(120));
+        assert!(trigger_order(&mut order, &path, start).is_none());
+
+        // 卖出追踪止损(已激活, 回调 10%): 最高价 110 回撤到 99 触发
+        let mut order = trigger(OrderType::TrailingStop, Side::Sell, dec!(100));
+        order.callback_rate = Some(dec!(0.1));
+        order.trail_price = Some(dec!(100));
+        let touch = trigger_order(&mut order, &path, start).unwrap();
+        assert_eq!(touch.price, dec!(99));
+        assert_eq!(touch.pos, dec!(1) + dec!(11) / dec!(15));
+        assert_eq!(order.trail_price, Some(dec!(110)));
+
+        // 卖出追踪止损(未激活): 到达激活价格 105 后开始追踪, 本根K线不触发
+        let mut order = trigger(OrderType::TrailingStop, Side::Sell, dec!(105));
+        order.callback_rate = Some(dec!(0.1));
+        assert!(trigger_order(&mut order, &path, start).is_none());
+        assert_eq!(order.trail_price, Some(dec!(110)));
+    }
+
+    #[test]
+    fn test_participation_rate() {
+        let mut bt = backtest(&[[100.0, 101.0, 99.0, 100.0, 10.0]; 4]);
+        bt.participation_rate = dec!(0.2);
+
+        let market = bt
+            .place_order(
+                "BTC",
+                OrderType::Market,
+                Direction::Long,
+                Side::Buy,
+                dec!(5),
+                None,
+            )
+            .unwrap();
+        let limit = bt
+            .place_order(
+                "BTC",
+                OrderType::Limit,
+                Direction::Long,
+                Side::Buy,
+                dec!(1),
+                Some(dec!(100)),
+            )
+            .unwrap();
+
+        // 每根K线最多成交 10 × 0.2 = 2, 先成交的市价单用完额度
+        step(&mut bt);
+        assert_eq!(
+            order_state(&bt, &market),
+            (OrderStatus::PartiallyFilled, dec!(2))
+        );
+        assert_eq!(order_state(&bt, &limit), (OrderStatus::Pending, dec!(0)));
+
+        step(&mut bt);
+        assert_eq!(
+            order_state(&bt, &market),
+            (OrderStatus::PartiallyFilled, dec!(4))
+        );
+
+        // 市价单剩余 1 成交后, 限价单使用剩余额度
+        step(&mut bt);
+        assert_eq!(order_state(&bt, &market), (OrderStatus::Filled, dec!(5)));
+        assert_eq!(order_state(&bt, &limit), (OrderStatus::Filled, dec!(1)));
+
+        let sizes = bt.trades.iter().map(|trade| trade.size).collect::<Vec<_>>();
+        assert_eq!(sizes, [dec!(2), dec!(2), dec!(1), dec!(1)]);
+        assert_eq!(bt.context.positions.get("BTC").unwrap().long.size, dec!(6));
+    }
+
+    #[test]
+    fn test_settle_funding() {
+        let funding_rates = || {
+            let time = time_from_millis(START_MS + 60_000).unwrap();
+            VecDeque::from([(time, dec!(0.00138))])
+        };
+
+        // 全仓: 按净持仓从资金中结算, 10 × 100 × 0.00138 = 1.38
+        let mut bt = backtest(&[[100.0, 101.0, 99.0, 100.0, 100.0]; 3]);
+        bt.funding_rates.insert("BTC".into(), funding_rates());
+        bt.place_order(
+            "BTC",
+            OrderType::Market,
+            Direction::Long,
+            Side::Buy,
+            dec!(10),
+            None,
+        )
+        .unwrap();
+        step(&mut bt);
+        assert_eq!(bt.context.cash, dec!(9998.62));
+        assert_eq!(bt.fundings.len(), 1);
+        assert_eq!(bt.fundings[0].cash, dec!(1.38));
+
+        // 逐仓: 多头从保证金中支付, 空头保证金增加, 资金不变
+        let mut bt = backtest(&[[100.0, 101.0, 99.0, 100.0, 100.0]; 3]);
+        bt.funding_rates.insert("BTC".into(), funding_rates());
+        bt.set_margin_mode("BTC", MarginMode::Isolated).unwrap();
+        bt.set_lever("BTC", 10).unwrap();
+        for (direction, side) in [(Direction::Long, Side::Buy), (Direction::Short, Side::Sell)] {
+            bt.place_order("BTC", OrderType::Market, direction, side, dec!(10), None)
+                .unwrap();
+        }
+        step(&mut bt);
+        assert_eq!(bt.context.cash, dec!(9800));
+        assert_eq!(bt.fundings[0].cash, dec!(0));
+
+        let pos = bt.context.positions.get("BTC").unwrap();
+        assert_eq!(pos.long.margin, dec!(98.62));
+        assert_eq!(pos.short.margin, dec!(101.38));
+        // 多头强平价格: (100 × 10 - 98.62) / (10 × (1 - 0.004)) = 90.5
+        assert_eq!(pos.long.liq_price, dec!(90.5));
+        assert_eq!(
+            pos.short.liq_price,
+            bt.context.calc_liq_price("BTC", Direction::Short)
+        );
+    }
+
+    #[test]
+    fn test_liquidation_after_fill() {
+        let mut bt = backtest(&[
+            [100.0, 130.0, 90.0, 95.0, 10000.0],
+            [100.0, 120.0, 95.0, 100.0, 10000.0],
+            [100.0, 101.0, 99.0, 100.0, 10000.0],
+        ]);
+        bt.set_lever("BTC", 10).unwrap();
+
+        // 价格路径 100 -> 130 -> 90 -> 95, 止损卖出在回落到 95 时开空
+        bt.place_trigger_order(
+            "BTC",
+            OrderType::StopMarket,
+            Direction::Short,
+            Side::Sell,
+            dec!(625),
+            None,
+            Some(dec!(95)),
+            None,
+        )
+        .unwrap();
+
+        // 强平价格: (95 × 625 + 10000) / (625 × (1 + 0.004)) ≈ 110.56, 开仓前的最高价 130 不触发强平
+        step(&mut bt);
+        let liq_price = bt.context.calc_liq_price("BTC", Direction::Short);
+        assert!(liq_price > dec!(110) && liq_price < dec!(111));
+        assert_eq!(
+            bt.context.positions.get("BTC").unwrap().short.size,
+            dec!(625)
+        );
+
+        // 下一根K线上涨到 120 时按强平价格平仓
+        step(&mut bt);
+        let symbol = bt.context.symbols.get("BTC").unwrap();
+        let trade = bt.trades.last().unwrap();
+        assert_eq!(trade.price, symbol.adverse_price(liq_price, Side::Buy));
+        assert!(
+            bt.context
+                .positions
+                .get("BTC")
+                .unwrap()
+                .short
+                .size
+                .is_zero()
+        );
+    }
+
+    #[test]
+    fn test_time_in_force() {
+        let mut bt = backtest(&[[100.0, 101.0, 99.0, 100.0, 10.0]; 3]);
+        bt.participation_rate = dec!(0.5);
+        let with_tif = |req: OrderRequest, tif| OrderRequest {
+            time_in_force: tif,
+            ..req
+        };
+
+        // IOC 以开盘价成交本根K线额度 5, 剩余部分取消
+        let ioc = with_tif(
+            limit(Direction::Long, Side::Buy, dec!(8), dec!(100)),
+            TimeInForce::Ioc,
+        );
+        let ioc = bt.submit_order(ioc).unwrap();
+        step(&mut bt);
+        assert_eq!(order_state(&bt, &ioc), (OrderStatus::Canceled, dec!(5)));
+
+        // FOK 无法全部成交时不成交; 开盘价未到达限价的 IOC 不在盘中成交
+        let fok = with_tif(
+            limit(Direction::Long, Side::Buy, dec!(6), dec!(100)),
+            TimeInForce::Fok,
+        );
+        let fok = bt.submit_order(fok).unwrap();
+        let ioc = with_tif(
+            limit(Direction::Long, Side::Buy, dec!(1), dec!(99.5)),
+            TimeInForce::Ioc,
+        );
+        let ioc = bt.submit_order(ioc).unwrap();
+        let gtc = bt
+            .submit_order(limit(Direction::Long, Side::Buy, dec!(5), dec!(99.5)))
+            .unwrap();
+        step(&mut bt);
+        assert_eq!(order_state(&bt, &fok), (OrderStatus::Canceled, dec!(0)));
+        assert_eq!(order_state(&bt, &ioc), (OrderStatus::Canceled, dec!(0)));
+        assert_eq!(order_state(&bt, &gtc), (OrderStatus::Filled, dec!(5)));
+    }
+
+    #[test]
+    fn test_post_only() {
+        let mut bt = backtest(&[
+            [100.0, 101.0, 99.8, 100.0, 10.0],
+            [99.0, 100.0, 98.0, 99.0, 10.0],
+            [99.0, 100.0, 98.0, 99.0, 10.0],
+        ]);
+        bt.fee_schedule = FeeSchedule::new(dec!(0.0002), dec!(0.0005)).unwrap();
+        let post_only = |price| OrderRequest {
+            post_only: true,
+            ..limit(Direction::Long, Side::Buy, dec!(1), price)
+        };
+
+        // 按当前价格即可成交时下单即拒绝
+        let crossed = bt.submit_order(post_only(dec!(100))).unwrap();
+        let order = bt.context.find_order(&crossed).unwrap();
+        assert_eq!(order.status, OrderStatus::Rejected);
+        assert_eq!(order.reason, Some(RejectReason::PostOnly));
+
+        // 盘中到达限价时按挂单费率成交
+        let maker = bt.submit_order(post_only(dec!(99.9))).unwrap();
+        step(&mut bt);
+        assert_eq!(order_state(&bt, &maker), (OrderStatus::Filled, dec!(1)));
+        assert_eq!(bt.trades[0].fee, dec!(99.9) * dec!(0.0002));
+
+        // 下一根K线跳空低开, 首次撮合即可成交时拒绝
+        let gapped = bt.submit_order(post_only(dec!(99.5))).unwrap();
+        step(&mut bt);
+        let order = bt.context.find_order(&gapped).unwrap();
+        assert_eq!(order.status, OrderStatus::Rejected);
+        assert_eq!(order.reason, Some(RejectReason::PostOnly));
+        assert_eq!(bt.trades.len(), 1);
+    }
+
+    #[test]
+    fn test_reduce_only() {
+        let mut bt = backtest(&[[100.0, 101.0, 99.0, 100.0, 10.0]; 3]);
+        let reduce_only = |direction, side, size| OrderRequest {
+            reduce_only: true,
+            ..OrderRequest::new("BTC", OrderType::Market, direction, side, size, None)
+        };
+
+        // 开仓方向的只减仓订单被拒绝
+        let open = bt
+            .submit_order(reduce_only(Direction::Long, Side::Buy, dec!(1)))
+            .unwrap();
+        assert_eq!(
+            bt.context.find_order(&open).unwrap().reason,
+            Some(RejectReason::ReduceOnly)
+        );
+
+        bt.place_order(
+            "BTC",
+            OrderType::Market,
+            Direction::Long,
+            Side::Buy,
+            dec!(2),
+            None,
+        )
+        .unwrap();
+        step(&mut bt);
+
+        // 数量截断为可平持仓, 成交后持仓平完
+        let close = bt
+            .submit_order(reduce_only(Direction::Long, Side::Sell, dec!(5)))
+            .unwrap();
+        assert_eq!(bt.context.find_order(&close).unwrap().size, dec!(2));
+        step(&mut bt);
+        assert_eq!(order_state(&bt, &close), (OrderStatus::Filled, dec!(2)));
+        assert!(bt.context.positions.get("BTC").unwrap().long.size.is_zero());
+    }
+
+    #[test]
+    fn test_latency() {
+        // 价格路径 100 -> 110 -> 90 -> 95, 每段 20 秒
+        let bars = [[100.0, 110.0, 90.0, 95.0, 100.0]; 3];
+
+        // 下单延迟 40 秒: 市价单在第二段末尾(90)成交; 延迟 90 秒: 在下一根K线 30 秒处(100)成交
+        let mut bt = backtest(&bars);
+        bt.latency = Latency::new(40_000, 0, 0, 0);
+        let fast = bt
+            .place_order(
+                "BTC",
+                OrderType::Market,
+                Direction::Long,
+                Side::Buy,
+                dec!(1),
+                None,
+            )
+            .unwrap();
+        bt.latency = Latency::new(90_000, 0, 0, 0);
+        let slow = bt
+            .place_order(
+                "BTC",
+                OrderType::Market,
+                Direction::Long,
+                Side::Buy,
+                dec!(1),
+                None,
+            )
+            .unwrap();
+        step(&mut bt);
+        assert_eq!(order_state(&bt, &fast), (OrderStatus::Filled, dec!(1)));
+        assert_eq!(order_state(&bt, &slow), (OrderStatus::New, dec!(0)));
+        assert_eq!(bt.trades[0].price, dec!(90));
+        step(&mut bt);
+        assert_eq!(order_state(&bt, &slow), (OrderStatus::Filled, dec!(1)));
+        assert_eq!(bt.trades[1].price, dec!(100));
+
+        // 撤单延迟 30 秒: 撤单生效前到达限价的订单成交, 之后到达的订单取消
+        let mut bt = backtest(&bars);
+        bt.latency = Latency::new(0, 30_000, 0, 0);
+        let early = bt
+            .submit_order(limit(Direction::Short, Side::Sell, dec!(1), dec!(105)))
+            .unwrap();
+        let late = bt
+            .submit_order(limit(Direction::Long, Side::Buy, dec!(1), dec!(91)))
+            .unwrap();
+        bt.cancel_orders(&[early.clone(), late.clone()]).unwrap();
+        assert_eq!(order_state(&bt, &late), (OrderStatus::Canceling, dec!(0)));
+        step(&mut bt);
+        assert_eq!(order_state(&bt, &early), (OrderStatus::Filled, dec!(1)));
+        assert_eq!(order_state(&bt, &late), (OrderStatus::Canceled, dec!(0)));
+    }
+
+    #[test]
+    fn test_isolated_margin() {
+        let mut bt = backtest(&[[100.0, 101.0, 99.0, 100.0, 100.0]; 3]);
+        bt.set_lever("BTC", 10).unwrap();
+        bt.place_order(
+            "BTC",
+            OrderType::Market,
+            Direction::Long,
+            Side::Buy,
+            dec!(10),
+            None,
+        )
+        .unwrap();
+        step(&mut bt);
+
+        // 全仓持仓不能调整保证金
+        assert!(bt.add_margin("BTC", Direction::Long, dec!(50)).is_err());
+
+        let mut bt = backtest(&[[100.0, 101.0, 99.0, 100.0, 100.0]; 3]);
+        bt.set_margin_mode("BTC", MarginMode::Isolated).unwrap();
+        bt.set_lever("BTC", 10).unwrap();
+        bt.place_order(
+            "BTC",
+            OrderType::Market,
+            Direction::Long,
+            Side::Buy,
+            dec!(10),
+            None,
+        )
+        .unwrap();
+        step(&mut bt);
+        let margin = |bt: &Backtest| bt.context.positions.get("BTC").unwrap().long.margin;
+        assert_eq!((margin(&bt), bt.context.cash), (dec!(100), dec!(9900)));
+
+        // 增加保证金后强平价格降低
+        let liq_price = bt.context.calc_liq_price("BTC", Direction::Long);
+        bt.add_margin("BTC", Direction::Long, dec!(50)).unwrap();
+        assert_eq!((margin(&bt), bt.context.cash), (dec!(150), dec!(9850)));
+        assert!(bt.context.calc_liq_price("BTC", Direction::Long) < liq_price);
+        assert!(bt.add_margin("BTC", Direction::Short, dec!(50)).is_err());
+
+        // 减少后不能低于初始保证金 100 × 10 / 10
+        assert!(bt.remove_margin("BTC", Direction::Long, dec!(60)).is_err());
+        bt.remove_margin("BTC", Direction::Long, dec!(50)).unwrap();
+        assert_eq!((margin(&bt), bt.context.cash), (dec!(100), dec!(9900)));
+        assert_eq!(bt.context.calc_liq_price("BTC", Direction::Long), liq_price);
+    }
+
+    #[test]
+    fn test_one_way_flip() {
+        let mut bt = backtest(&[
+            [100.0, 101.0, 99.0, 100.0, 100.0],
+            [110.0, 111.0, 109.0, 110.0, 100.0],
+            [110.0, 111.0, 109.0, 110.0, 100.0],
+        ]);
+        bt.context.position_mode = PositionMode::OneWay;
+        bt.fee_schedule = FeeSchedule::new(dec!(0.0002), dec!(0.0005)).unwrap();
+
+        bt.place_order(
+            "BTC",
+            OrderType::Market,
+            Direction::Long,
+            Side::Buy,
+            dec!(2),
+            None,
+        )
+        .unwrap();
+        step(&mut bt);
+
+        // 卖出 5: 平多 2 (盈亏 20), 剩余 3 开空; 手续费 550 × 0.0005 按数量分摊
+        bt.place_order(
+            "BTC",
+            OrderType::Market,
+            Direction::Long,
+            Side::Sell,
+            dec!(5),
+            None,
+        )
+        .unwrap();
+        step(&mut bt);
+
+        let legs = bt.trades[1..]
+            .iter()
+            .map(|trade| (trade.direction, trade.size, trade.fee, trade.rpl))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            legs,
+            [
+                (Direction::Long, dec!(2), dec!(0.11), dec!(19.89)),
+                (Direction::Short, dec!(3), dec!(0.165), dec!(0)),
+            ]
+        );
+
+        let pos = bt.context.positions.get("BTC").unwrap();
+        assert!(pos.long.size.is_zero());
+        assert_eq!((pos.short.size, pos.short.price), (dec!(3), dec!(110)));
+        assert_eq!(bt.context.cash, dec!(10019.625));
+    }
+
+    #[test]
+    fn test_history_len() {
+        let mut bt = backtest(&[[100.0, 101.0, 99.0, 100.0, 100.0]; 4]);
+        bt.context.history_len = 2;
+
+        let ids = (0..3)
+            .map(|_| {
+                let id = bt
+                    .place_order(
+                        "BTC",
+                        OrderType::Market,
+                        Direction::Long,
+                        Side::Buy,
+                        dec!(1),
+                        None,
+                    )
+                    .unwrap();
+                step(&mut bt);
+                id
+            })
+            .collect::<Vec<_>>();
+
+        // 上下文只保留最近 2 条历史订单及成交, 回测成交记录不受影响
+        let history = bt
+            .context
+            .history_orders
+            .iter()
+            .map(|order| order.id.clone())
+            .collect::<Vec<_>>();
+        assert_eq!(history, ids[1..]);
+        assert_eq!(bt.context.trades.len(), 2);
+        assert_eq!(bt.trades.len(), 3);
+        assert!(bt.context.find_order(&ids[0]).is_none());
+
+        // 保留数量为 0 时不保留历史订单及成交
+        let mut bt = backtest(&[[100.0, 101.0, 99.0, 100.0, 100.0]; 2]);
+        bt.context.history_len = 0;
+        bt.place_order(
+            "BTC",
+            OrderType::Market,
+            Direction::Long,
+            Side::Buy,
+            dec!(1),
+            None,
+        )
+        .unwrap();
+        step(&mut bt);
+        assert!(bt.context.history_orders.is_empty() && bt.context.trades.is_empty());
+        assert_eq!(bt.trades.len(), 1);
+    }
+
+    #[test]
+    fn test_reject_reason() {
+        let mut bt = backtest(&[[100.0, 101.0, 99.0, 100.0, 100.0]; 2]);
+        let symbol = bt.context.symbols.get_mut("BTC").unwrap();
+        symbol.price_tick = dec!(0.1);
+        symbol.min_size = dec!(0.01);
+        symbol.min_cash = dec!(5);
+        symbol.margin_tiers = vec![
+            MarginTier {
+                max_cash: dec!(5000),
+                mmr: dec!(0.004),
+                mm_amount: dec!(0),
+                max_lever: dec!(10),
+            },
+            MarginTier {
+                max_cash: Decimal::MAX,
+                mmr: dec!(0.01),
+                mm_amount: dec!(30),
+                max_lever: dec!(5),
+            },
+        ];
+
+        let mut reason = |req: OrderRequest| {
+            let id = bt.submit_order(req).unwrap();
+            bt.context.find_order(&id).unwrap().reason
+        };
+        let buy = |size, price| limit(Direction::Long, Side::Buy, size, price);
+
+        assert_eq!(
+            reason(OrderRequest {
+                code: "ETH".into(),
+                ..buy(dec!(1), dec!(100))
+            }),
+            Some(RejectReason::UnknownSymbol)
+        );
+        assert_eq!(
+            reason(buy(dec!(1), dec!(100.05))),
+            Some(RejectReason::InvalidTick)
+        );
+        assert_eq!(
+            reason(buy(dec!(0.001), dec!(100))),
+            Some(RejectReason::BelowMinSize)
+        );
+        assert_eq!(
+            reason(buy(dec!(0.01), dec!(100))),
+            Some(RejectReason::BelowMinNotional)
+        );
+        assert_eq!(
+            reason(buy(dec!(200), dec!(100))),
+            Some(RejectReason::InsufficientMargin)
+        );
+        assert_eq!(
+            reason(limit(Direction::Long, Side::Sell, dec!(1), dec!(100))),
+            Some(RejectReason::InsufficientPosition)
+        );
+        assert_eq!(reason(buy(dec!(1), dec!(100))), None);
+
+        // 名义价值 6000 超过第一层上限, 第二层最大杠杆为 5
+        bt.set_lever("BTC", 10).unwrap();
+        let id = bt.submit_order(buy(dec!(60), dec!(100))).unwrap();
+        assert_eq!(
+            bt.context.find_order(&id).unwrap().reason,
+            Some(RejectReason::ExceedMaxLever)
+        );
+    }
+
+    #[test]
+    fn test_amend_order() {
+        let bars = [
+            [100.0, 101.0, 99.0, 100.0, 10.0],
+            [99.0, 100.0, 98.0, 99.0, 10.0],
+            [99.0, 100.0, 98.0, 99.0, 10.0],
+        ];
+        let mut bt = backtest(&bars);
+        bt.fee_schedule = FeeSchedule::new(dec!(0.0002), dec!(0.0005)).unwrap();
+        let post_only = bt
+            .submit_order(OrderRequest {
+                post_only: true,
+                ..limit(Direction::Long, Side::Buy, dec!(1), dec!(95))
+            })
+            .unwrap();
+        let plain = bt
+            .submit_order(limit(Direction::Long, Side::Buy, dec!(1), dec!(95)))
+            .unwrap();
+        step(&mut bt);
+
+        // 改单后按当前价格即可成交的只做挂单直接拒绝改单
+        assert!(bt.amend_order(&post_only, dec!(101), dec!(1)).is_err());
+        assert_eq!(
+            order_state(&bt, &post_only),
+            (OrderStatus::Pending, dec!(0))
+        );
+
+        // 改价后按新订单撮合: 下一根K线开盘价 99 越过限价, 只做挂单被拒绝, 普通限价单按吃单成交
+        bt.amend_order(&post_only, dec!(99.5), dec!(1)).unwrap();
+        bt.amend_order(&plain, dec!(101), dec!(1)).unwrap();
+        assert_eq!(order_state(&bt, &plain), (OrderStatus::New, dec!(0)));
+        step(&mut bt);
+
+        let order = bt.context.find_order(&post_only).unwrap();
+        assert_eq!(order.status, OrderStatus::Rejected);
+        assert_eq!(order.reason, Some(RejectReason::PostOnly));
+        assert_eq!(order_state(&bt, &plain), (OrderStatus::Filled, dec!(1)));
+        assert_eq!(bt.trades.len(), 1);
+        assert_eq!(bt.trades[0].price, dec!(99));
+        assert_eq!(bt.trades[0].fee, dec!(99) * dec!(0.0005));
+    }
+
+    #[test]
+    fn test_amend_partially_filled() {
+        let mut bt = backtest(&[[100.0, 101.0, 99.0, 100.0, 10.0]; 4]);
+        bt.participation_rate = dec!(0.1);
+        let first = bt
+            .submit_order(limit(Direction::Long, Side::Buy, dec!(3), dec!(100)))
+            .unwrap();
+        let second = bt
+            .submit_order(limit(Direction::Long, Side::Buy, dec!(3), dec!(100)))
+            .unwrap();
+        step(&mut bt);
+        assert_eq!(
+            order_state(&bt, &first),
+            (OrderStatus::PartiallyFilled, dec!(1))
+        );
+
+        // 价格不变减少数量保留优先级; 改价后排到其他订单之后, 保留已成交数量
+        bt.amend_order(&first, dec!(100), dec!(2)).unwrap();
+        assert_eq!(
+            order_state(&bt, &first),
+            (OrderStatus::PartiallyFilled, dec!(1))
+        );
+        bt.amend_order(&first, dec!(99.5), dec!(2)).unwrap();
+        assert_eq!(order_state(&bt, &first), (OrderStatus::New, dec!(1)));
+        step(&mut bt);
+        assert_eq!(
+            order_state(&bt, &first),
+            (OrderStatus::PartiallyFilled, dec!(1))
+        );
+        assert_eq!(
+            order_state(&bt, &second),
+            (OrderStatus::PartiallyFilled, dec!(1))
+        );
+        assert!(bt.amend_order(&first, dec!(99.5), dec!(1)).is_err());
+    }
+
+    #[test]
+    fn test_batch_orders() {
+        let mut bt = backtest(&[[100.0, 101.0, 99.0, 100.0, 10.0]; 2]);
+
+        // 任一订单参数错误时撤回本批次已提交的订单
+        let invalid = OrderRequest::new(
+            "BTC",
+            OrderType::Market,
+            Direction::Long,
+            Side::Buy,
+            dec!(1),
+            Some(dec!(100)),
+        );
+        let batch = vec![
+            limit(Direction::Long, Side::Buy, dec!(1), dec!(95)),
+            invalid,
+        ];
+        assert!(bt.place_orders(batch).is_err());
+        assert!(bt.context.orders.is_empty());
+
+        let ids = bt
+            .place_orders(vec![
+                limit(Direction::Long, Side::Buy, dec!(1), dec!(95)),
+                limit(Direction::Long, Side::Buy, dec!(1000), dec!(95)),
+            ])
+            .unwrap();
+        assert_eq!(order_state(&bt, &ids[1]).0, OrderStatus::Rejected);
+
+        // 任一订单无法撤销时全部不撤
+        assert!(bt.cancel_orders(&ids).is_err());
+        assert_eq!(order_state(&bt, &ids[0]).0, OrderStatus::New);
+        bt.cancel_orders(&ids[..1]).unwrap();
+        step(&mut bt);
+        assert_eq!(order_state(&bt, &ids[0]).0, OrderStatus::Canceled);
+    }
+
+    #[test]
+    fn test_oco() {
+        let mut bt = backtest(&[[100.0, 101.0, 99.0, 100.0, 10.0]; 2]);
+
+        // 价格路径 100 -> 101 -> 99 -> 100: 卖出限价 100.5 先成交, 撤销买入限价 99.5
+        let ids = bt
+            .place_oco(
+                limit(Direction::Short, Side::Sell, dec!(1), dec!(100.5)),
+                limit(Direction::Long, Side::Buy, dec!(1), dec!(99.5)),
+            )
+            .unwrap();
+        step(&mut bt);
+        assert_eq!(order_state(&bt, &ids[0]), (OrderStatus::Filled, dec!(1)));
+        assert_eq!(order_state(&bt, &ids[1]), (OrderStatus::Canceled, dec!(0)));
+
+        // 任一订单被拒绝时撤销另一订单
+        let mut bt = backtest(&[[100.0, 101.0, 99.0, 100.0, 10.0]; 2]);
+        let ids = bt
+            .place_oco(
+                limit(Direction::Long, Side::Buy, dec!(1000), dec!(99.5)),
+                limit(Direction::Short, Side::Sell, dec!(1), dec!(100.5)),
+            )
+            .unwrap();
+        assert_eq!(order_state(&bt, &ids[0]).0, OrderStatus::Rejected);
+        assert_eq!(order_state(&bt, &ids[1]).0, OrderStatus::Canceled);
+    }
+
+    #[test]
+    fn test_bracket() {
+        let mut bt = backtest(&[
+            [100.0, 101.0, 99.0, 100.0, 10.0],
+            [100.0, 111.0, 99.0, 105.0, 10.0],
+            [105.0, 106.0, 104.0, 105.0, 10.0],
+        ]);
+        let entry = OrderRequest::new(
+            "BTC",
+            OrderType::Market,
+            Direction::Long,
+            Side::Buy,
+            dec!(2),
+            None,
+        );
+        let ids = bt.place_bracket(entry, dec!(110), dec!(95)).unwrap();
+        assert_eq!(order_state(&bt, &ids[1]).0, OrderStatus::Waiting);
+
+        // 入场单成交后按成交数量激活止盈止损单
+        step(&mut bt);
+        let take_profit = bt.context.find_order(&ids[1]).unwrap();
+        assert_eq!(
+            (take_profit.status, take_profit.size),
+            (OrderStatus::New, dec!(2))
+        );
+
+        // 止盈成交后撤销止损
+        step(&mut bt);
+        assert_eq!(order_state(&bt, &ids[1]), (OrderStatus::Filled, dec!(2)));
+        assert_eq!(order_state(&bt, &ids[2]), (OrderStatus::Canceled, dec!(0)));
+        assert_eq!(bt.trades.last().unwrap().price, dec!(110));
+        assert!(bt.context.positions.get("BTC").unwrap().long.size.is_zero());
+
+        // 入场单未成交即撤销时撤销止盈止损单
+        let entry = limit(Direction::Long, Side::Buy, dec!(1), dec!(90));
+        let ids = bt.place_bracket(entry, dec!(110), dec!(80)).unwrap();
+        bt.cancel_order(&ids[0]).unwrap();
+        step(&mut bt);
+        for id in &ids {
+            assert_eq!(order_state(&bt, id), (OrderStatus::Canceled, dec!(0)));
+        }
+    }
+
+    #[test]
+    fn test_tag_attribution() {
+        let mut bt = backtest(&[
+            [100.0, 101.0, 99.0, 100.0, 100.0],
+            [110.0, 111.0, 109.0, 110.0, 100.0],
+            [120.0, 121.0, 119.0, 120.0, 100.0],
+        ]);
+        let market = |side, size, tag: Option<&str>| OrderRequest {
+            tag: tag.map(String::from),
+            ..OrderRequest::new("BTC", OrderType::Market, Direction::Long, side, size, None)
+        };
+        let tag = |name: &str| Some(name.to_string());
+
+        bt.submit_order(market(Side::Buy, dec!(1), Some("a")))
+            .unwrap();
+        bt.submit_order(market(Side::Buy, dec!(2), Some("b")))
+            .unwrap();
+        step(&mut bt);
+
+        // 平仓按先开先平对应开仓标签, 与平仓订单自身标签无关
+        bt.submit_order(market(Side::Sell, dec!(2), None)).unwrap();
+        step(&mut bt);
+        assert_eq!(
+            bt.trades[2].entry_tags,
+            [(tag("a"), dec!(1)), (tag("b"), dec!(1))]
+        );
+
+        bt.submit_order(market(Side::Sell, dec!(1), Some("c")))
+            .unwrap();
+        step(&mut bt);
+        assert_eq!(bt.trades[3].entry_tags, [(tag("b"), dec!(1))]);
+
+        // 标签 a 平仓盈亏 10, 标签 b 平仓盈亏 10 + 20
+        let report = Report::new(
+            10000.0,
+            10040.0,
+            &[10000.0, 10020.0, 10040.0],
+            bt.start_time,
+            bt.curr_time,
+            bt.interval,
+            &bt.trades,
+            &bt.fundings,
+        )
+        .unwrap();
+        let pnls = report
+            .tags
+            .iter()
+            .map(|(tag, report)| (tag.as_str(), report.pnl, report.trade_cnt))
+            .collect::<Vec<_>>();
+        assert_eq!(pnls, [("a", 10.0, 1), ("b", 30.0, 2)]);
+    }
+
+    #[test]
+    fn test_limit_tick_slippage() {
+        let mut bt = backtest(&[[100.0, 110.0, 90.0, 95.0, 100.0]; 2]);
+        bt.context.symbols.get_mut("BTC").unwrap().price_tick = dec!(1);
+
+        // 下单延迟 5 秒: 生效时价格 102.5 已低于限价, 按 103 成交, 取整差额计入滑点
+        bt.latency = Latency::new(5_000, 0, 0, 0);
+        bt.submit_order(limit(Direction::Long, Side::Buy, dec!(2), dec!(107)))
+            .unwrap();
+        step(&mut bt);
+        let trade = &bt.trades[0];
+        assert_eq!((trade.price, trade.slippage), (dec!(103), dec!(1)));
     }
 }
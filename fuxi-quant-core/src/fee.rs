@@ -0,0 +1,200 @@
+use crate::types::*;
+use anyhow::{Result, ensure};
+use chrono::Duration;
+use rust_decimal::prelude::*;
+use std::collections::VecDeque;
+
+/// 手续费率
+#[derive(Debug, Clone, Copy)]
+pub struct FeeRate {
+    /// 挂单手续费率(负数为返佣)
+    pub maker: Decimal,
+    /// 吃单手续费率
+    pub taker: Decimal,
+}
+
+impl FeeRate {
+    pub fn new(maker: Decimal, taker: Decimal) -> Result<Self> {
+        ensure!(maker > -Decimal::ONE && maker < Decimal::ONE);
+        ensure!(!taker.is_sign_negative() && taker < Decimal::ONE);
+        Ok(Self { maker, taker })
+    }
+}
+
+/// 手续费等级(VIP)
+#[derive(Debug, Clone, Copy)]
+pub struct FeeTier {
+    /// 30日成交额下限
+    pub min_volume: Decimal,
+    /// 手续费率
+    pub rate: FeeRate,
+}
+
+/// 手续费方案
+#[derive(Debug, Clone)]
+pub struct FeeSchedule {
+    /// 手续费等级(按30日成交额下限升序)
+    pub tiers: Vec<FeeTier>,
+    /// 交易对手续费率(优先于等级费率)
+    pub overrides: Map<String, FeeRate>,
+    /// 使用其他资产抵扣手续费的折扣比例(如 0.1 表示九折, 不作用于返佣)
+    pub discount: Decimal,
+    /// 成交额统计窗口
+    pub window: Duration,
+    /// 窗口内成交记录
+    volumes: VecDeque<(Time, Decimal)>,
+    /// 窗口内成交额
+    volume: Decimal,
+}
+
+impl FeeSchedule {
+    pub fn new(maker_fee_rate: Decimal, taker_fee_rate: Decimal) -> Result<Self> {
+        Ok(Self {
+            tiers: vec![FeeTier {
+                min_volume: Decimal::ZERO,
+                rate: FeeRate::new(maker_fee_rate, taker_fee_rate)?,
+            }],
+            overrides: Default::default(),
+            discount: Decimal::ZERO,
+            window: Duration::days(30),
+            volumes: Default::default(),
+            volume: Decimal::ZERO,
+        })
+    }
+
+    /// 添加手续费等级
+    pub fn add_tier(&mut self, min_volume: Decimal, rate: FeeRate) -> Result<()> {
+        ensure!(!min_volume.is_sign_negative());
+        ensure!(
+            self.tiers.iter().all(|tier| tier.min_volume != min_volume),
+            "duplicate fee tier: {min_volume}"
+        );
+        self.tiers.push(FeeTier { min_volume, rate });
+        self.tiers.sort_by_key(|tier| tier.min_volume);
+        Ok(())
+    }
+
+    /// 设置交易对手续费率
+    pub fn set_override(&mut self, code: &str, rate: FeeRate) {
+        self.overrides.insert(code.to_string(), rate);
+    }
+
+    /// 设置手续费抵扣折扣
+    pub fn set_discount(&mut self, discount: Decimal) -> Result<()> {
+        ensure!(!discount.is_sign_negative() && discount < Decimal::ONE);
+        self.discount = discount;
+        Ok(())
+    }
+
+    /// 当前30日成交额
+    pub fn volume(&self) -> Decimal {
+        self.volume
+    }
+
+    /// 当前适用的手续费等级
+    pub fn tier(&self) -> &FeeTier {
+        self.tiers
+            .iter()
+            .rev()
+            .find(|tier| self.volume >= tier.min_volume)
+            .unwrap_or(&self.tiers[0])
+    }
+
+    /// 交易对当前适用的手续费率
+    pub fn rate(&self, code: &str) -> FeeRate {
+        self.overrides
+            .get(code)
+            .copied()
+            .unwrap_or_else(|| self.tier().rate)
+    }
+
+    /// 计算成交手续费并累计成交额
+    pub fn calc_fee(&mut self, time: Time, code: &str, cash: Decimal, is_maker: bool) -> Decimal {
+        // 移出统计窗口外的成交
+        while let Some((t, v)) = self.volumes.front()
+            && *t <= time - self.window
+        {
+            self.volume -= *v;
+            self.volumes.pop_front();
+        }
+
+        let rate = self.rate(code);
+        let fee_rate = if is_maker { rate.maker } else { rate.taker };
+        let mut fee = cash * fee_rate;
+        if fee > Decimal::ZERO {
+            fee *= Decimal::ONE - self.discount;
+        }
+
+        self.volumes.push_back((time, cash));
+        self.volume += cash;
+
+        fee
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::helpers::time_from_millis;
+
+    /// 第 n 天的成交时间
+    fn day(n: i64) -> Time {
+        time_from_millis(n * 86_400_000).unwrap()
+    }
+
+    #[test]
+    fn test_fee_tier_window() {
+        let mut schedule = FeeSchedule::new(dec!(0.0002), dec!(0.0005)).unwrap();
+        let vip = FeeRate::new(dec!(0.0001), dec!(0.0003)).unwrap();
+        schedule.add_tier(dec!(1000000), vip).unwrap();
+
+        // 成交前30日成交额为 0, 按基础费率
+        assert_eq!(
+            schedule.calc_fee(day(0), "BTC", dec!(1000000), false),
+            dec!(500)
+        );
+        assert_eq!(schedule.volume(), dec!(1000000));
+
+        // 30日成交额达到 100 万, 按 VIP 费率
+        assert_eq!(
+            schedule.calc_fee(day(1), "BTC", dec!(1000), false),
+            dec!(0.3)
+        );
+
+        // 第一笔成交移出统计窗口后恢复基础费率
+        assert_eq!(
+            schedule.calc_fee(day(30), "BTC", dec!(1000), false),
+            dec!(0.5)
+        );
+        assert_eq!(schedule.volume(), dec!(2000));
+    }
+
+    #[test]
+    fn test_fee_discount_and_rebate() {
+        let mut schedule = FeeSchedule::new(dec!(0.0002), dec!(0.0005)).unwrap();
+        schedule.set_discount(dec!(0.1)).unwrap();
+        schedule.set_override("ETH", FeeRate::new(dec!(-0.0001), dec!(0.0004)).unwrap());
+
+        // 折扣作用于手续费, 不作用于返佣
+        assert_eq!(
+            schedule.calc_fee(day(0), "BTC", dec!(1000), false),
+            dec!(0.45)
+        );
+        assert_eq!(
+            schedule.calc_fee(day(0), "BTC", dec!(1000), true),
+            dec!(0.18)
+        );
+        assert_eq!(
+            schedule.calc_fee(day(0), "ETH", dec!(1000), true),
+            dec!(-0.1)
+        );
+        assert_eq!(
+            schedule.calc_fee(day(0), "ETH", dec!(1000), false),
+            dec!(0.36)
+        );
+
+        assert!(FeeRate::new(dec!(-1), dec!(0.0005)).is_err());
+        assert!(FeeRate::new(dec!(0.0002), dec!(-0.0005)).is_err());
+        assert!(schedule.set_discount(dec!(1)).is_err());
+    }
+}
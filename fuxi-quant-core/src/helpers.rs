@@ -1,5 +1,5 @@
 use anyhow::{Result, anyhow, bail};
-use chrono::{DateTime, NaiveDateTime, TimeZone, Utc};
+use chrono::{DateTime, Duration, NaiveDateTime, TimeZone, Utc};
 use chrono_tz::Tz;
 use polars::frame::DataFrame;
 use snowflaked::sync::Generator;
@@ -51,6 +51,32 @@ pub fn time_from_millis(millis: i64) -> Result<DateTime<Tz>> {
     Ok(dt_utc.with_timezone(&TIME_TZ))
 }
 
+/// 解析K线周期, 如: 1s, 1m, 5m, 1h, 1d
+pub fn interval_from_str(s: &str) -> Result<Duration> {
+    let s = s.trim();
+    let idx = s
+        .find(|c: char| !c.is_ascii_digit())
+        .ok_or_else(|| anyhow!("invalid interval: {}", s))?;
+    let (num, unit) = s.split_at(idx);
+    let num = num
+        .parse::<i64>()
+        .map_err(|_| anyhow!("invalid interval: {}", s))?;
+
+    let interval = match unit {
+        "s" => Duration::seconds(num),
+        "m" => Duration::minutes(num),
+        "h" => Duration::hours(num),
+        "d" => Duration::days(num),
+        _ => bail!("invalid interval: {}", s),
+    };
+
+    if interval <= Duration::zero() {
+        bail!("invalid interval: {}", s);
+    }
+
+    Ok(interval)
+}
+
 #[inline]
 pub fn time_to_str(time: &DateTime<Tz>, fmt: Option<&str>) -> String {
     time.format(fmt.unwrap_or(TIME_FMT)).to_string()
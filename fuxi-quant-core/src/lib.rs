@@ -1,5 +1,9 @@
 #![allow(clippy::too_many_arguments)]
 
 pub mod backtest;
+pub mod fee;
 pub mod helpers;
+pub mod slippage;
+pub mod source;
+pub mod symbols;
 pub mod types;
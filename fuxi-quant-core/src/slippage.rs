@@ -0,0 +1,117 @@
+use crate::{helpers::*, types::*};
+use anyhow::{Result, ensure};
+use polars::frame::DataFrame;
+use rust_decimal::prelude::*;
+
+/// 固定滑点: 按基点计算
+pub struct FixedSlippage {
+    /// 滑点比例
+    pub rate: Decimal,
+}
+
+impl FixedSlippage {
+    pub fn new(bps: Decimal) -> Result<Self> {
+        ensure!(!bps.is_sign_negative());
+        Ok(Self {
+            rate: bps / Decimal::from(10000),
+        })
+    }
+}
+
+impl SlippageModel for FixedSlippage {
+    fn calc(
+        &mut self,
+        _: &Order,
+        _: Decimal,
+        _: Decimal,
+        _: &DataFrame,
+        _: usize,
+    ) -> Result<Decimal> {
+        Ok(self.rate)
+    }
+}
+
+/// 价差滑点: 以K线最高/最低价估算买卖价差, 按半个价差计算
+pub struct SpreadSlippage {
+    /// 价差系数(价差 = 系数 × 振幅)
+    pub factor: Decimal,
+}
+
+impl SpreadSlippage {
+    pub fn new(factor: Decimal) -> Result<Self> {
+        ensure!(!factor.is_sign_negative());
+        Ok(Self { factor })
+    }
+}
+
+impl SlippageModel for SpreadSlippage {
+    fn calc(
+        &mut self,
+        _: &Order,
+        _: Decimal,
+        _: Decimal,
+        bars: &DataFrame,
+        bar_idx: usize,
+    ) -> Result<Decimal> {
+        let high = df_f64(bars, "high", bar_idx)?;
+        let low = df_f64(bars, "low", bar_idx)?;
+        let mid = (high + low) / 2.0;
+        if mid <= 0.0 {
+            return Ok(Decimal::ZERO);
+        }
+
+        let spread = (high - low) / mid * self.factor.to_f64().unwrap_or_default();
+        Ok(Decimal::try_from(spread / 2.0)?)
+    }
+}
+
+/// 平方根冲击滑点: 滑点 = 系数 × 波动率 × sqrt(成交数量 / K线成交量)
+///
+/// 波动率以K线振幅相对成交均价(成交额 / 成交量)估算。
+pub struct SqrtImpactSlippage {
+    /// 冲击系数
+    pub coef: Decimal,
+}
+
+impl SqrtImpactSlippage {
+    pub fn new(coef: Decimal) -> Result<Self> {
+        ensure!(!coef.is_sign_negative());
+        Ok(Self { coef })
+    }
+}
+
+impl SlippageModel for SqrtImpactSlippage {
+    fn calc(
+        &mut self,
+        _: &Order,
+        price: Decimal,
+        size: Decimal,
+        bars: &DataFrame,
+        bar_idx: usize,
+    ) -> Result<Decimal> {
+        let high = df_f64(bars, "high", bar_idx)?;
+        let low = df_f64(bars, "low", bar_idx)?;
+        let bar_size = df_f64(bars, "size", bar_idx)?;
+        let bar_cash = df_f64(bars, "cash", bar_idx)?;
+
+        let vwap = if bar_size > 0.0 {
+            bar_cash / bar_size
+        } else {
+            price.to_f64().unwrap_or_default()
+        };
+        if vwap <= 0.0 {
+            return Ok(Decimal::ZERO);
+        }
+
+        let volatility = (high - low) / vwap;
+        // 无成交量时视为全部参与
+        let participation = if bar_size > 0.0 {
+            size.to_f64().unwrap_or_default() / bar_size
+        } else {
+            1.0
+        };
+
+        let rate = self.coef.to_f64().unwrap_or_default() * volatility * participation.sqrt();
+        Ok(Decimal::try_from(rate)?)
+    }
+}
@@ -0,0 +1,516 @@
+use crate::{
+    backtest::{bars, funding},
+    helpers::*,
+    symbols::SymbolStore,
+    types::*,
+};
+use anyhow::{Result, anyhow, bail, ensure};
+use polars::prelude::*;
+use rust_decimal::Decimal;
+use std::{collections::VecDeque, path::Path};
+use strum::{Display, EnumString};
+
+/// 币安历史数据缓存(数据目录下的 {market}/symbols.json, {market}/bars/{code}.data, {market}/funding/{code}.data)
+pub struct BinanceSource {
+    /// 数据目录
+    pub data_dir: String,
+    /// 市场
+    pub market: Market,
+    /// 交易对元数据
+    pub symbols: SymbolStore,
+}
+
+impl BinanceSource {
+    pub fn new(data_dir: &str, market: Market) -> Result<Self> {
+        market.migrate_legacy_dir(data_dir)?;
+        Ok(Self {
+            data_dir: data_dir.to_string(),
+            market,
+            symbols: SymbolStore::load(data_dir, market)?,
+        })
+    }
+}
+
+impl DataSource for BinanceSource {
+    fn load(
+        &self,
+        code: &str,
+        start_time: Time,
+        end_time: Time,
+        interval: &str,
+        history_bar_len: usize,
+    ) -> Result<DataFrame> {
+        bars::load(
+            &self.data_dir,
+            self.market,
+            code,
+            start_time,
+            end_time,
+            interval,
+            history_bar_len,
+        )
+    }
+
+    fn symbol(&self, code: &str) -> Result<Option<Symbol>> {
+        self.symbols.get(code).map(Some)
+    }
+
+    fn funding_rates(
+        &self,
+        code: &str,
+        end_time: Time,
+    ) -> Result<Option<VecDeque<(Time, Decimal)>>> {
+        funding::load(&self.data_dir, self.market, code, end_time)
+    }
+
+    fn sub_bars(&self, code: &str, start_time: Time, end_time: Time) -> Result<Option<SubBars>> {
+        bars::load_sub_bars(&self.data_dir, self.market, code, start_time, end_time)
+    }
+}
+
+/// 用户提供的交易对及资金费率(非币安数据源)
+#[derive(Default)]
+pub struct Metadata {
+    /// 交易对
+    pub symbols: Map<String, Symbol>,
+    /// 历史资金费率(结算时间, 费率)
+    pub funding_rates: Map<String, VecDeque<(Time, Decimal)>>,
+}
+
+impl Metadata {
+    /// 添加交易对
+    pub fn insert_symbol(&mut self, symbol: Symbol) {
+        self.symbols.insert(symbol.code.clone(), symbol);
+    }
+
+    /// 添加交易对的历史资金费率
+    pub fn insert_funding_rates(&mut self, code: &str, rates: VecDeque<(Time, Decimal)>) {
+        self.funding_rates.insert(code.to_string(), rates);
+    }
+
+    fn symbol(&self, code: &str) -> Option<Symbol> {
+        self.symbols.get(code).cloned()
+    }
+
+    fn funding_rates(&self, code: &str, end_time: Time) -> Option<VecDeque<(Time, Decimal)>> {
+        self.funding_rates.get(code).map(|rates| {
+            rates
+                .iter()
+                .copied()
+                .take_while(|(time, _)| *time <= end_time)
+                .collect()
+        })
+    }
+}
+
+/// 整数时间戳单位
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Display, EnumString)]
+#[strum(serialize_all = "snake_case")]
+pub enum EpochUnit {
+    /// 秒
+    S,
+    /// 毫秒
+    Ms,
+    /// 微秒
+    Us,
+    /// 纳秒
+    Ns,
+}
+
+/// 列映射: K线字段对应的数据列名
+#[derive(Debug, Clone)]
+pub struct ColumnMapping {
+    /// 时间列(整数时间戳, 日期时间或字符串)
+    pub time: String,
+    /// 整数时间戳单位
+    pub time_unit: EpochUnit,
+    /// 字符串时间格式(不含时区的时间按 Asia/Shanghai 解析)
+    pub time_format: String,
+    /// 开盘价
+    pub open: String,
+    /// 最高价
+    pub high: String,
+    /// 最低价
+    pub low: String,
+    /// 收盘价
+    pub close: String,
+    /// 成交量
+    pub size: String,
+    /// 成交额(缺省时按收盘价 × 成交量估算)
+    pub cash: Option<String>,
+    /// 成交笔数(缺省时为 0)
+    pub trades: Option<String>,
+    /// 主动买入成交量(缺省时为 0)
+    pub taker_size: Option<String>,
+    /// 主动买入成交额(缺省时为 0)
+    pub taker_cash: Option<String>,
+}
+
+impl Default for ColumnMapping {
+    fn default() -> Self {
+        Self {
+            time: "time".into(),
+            time_unit: EpochUnit::Ms,
+            time_format: TIME_FMT.into(),
+            open: "open".into(),
+            high: "high".into(),
+            low: "low".into(),
+            close: "close".into(),
+            size: "size".into(),
+            cash: Some("cash".into()),
+            trades: Some("trades".into()),
+            taker_size: Some("taker_size".into()),
+            taker_cash: Some("taker_cash".into()),
+        }
+    }
+}
+
+impl ColumnMapping {
+    /// 按列映射转换为标准K线字段, 并按时间排序
+    pub fn apply(&self, df: LazyFrame) -> Result<LazyFrame> {
+        let mut df = df;
+        let schema = df.collect_schema()?;
+        let column = |name: &str| -> Result<Expr> {
+            ensure!(schema.contains(name), "K线数据缺少列: {name}");
+            Ok(col(name))
+        };
+        let optional = |name: &Option<String>, default: Expr| match name {
+            Some(name) if schema.contains(name) => col(name.as_str()),
+            _ => default,
+        };
+
+        let time_tz = Some(polars::datatypes::TimeZone::from_chrono(&TIME_TZ));
+        let time_dtype = DataType::Datetime(TimeUnit::Milliseconds, time_tz.clone());
+        let time = column(&self.time)?;
+        let time = match schema.get(&self.time).unwrap() {
+            dtype if dtype.is_integer() => {
+                let millis = match self.time_unit {
+                    EpochUnit::S => time.cast(DataType::Int64) * lit(1000i64),
+                    EpochUnit::Ms => time.cast(DataType::Int64),
+                    EpochUnit::Us => time.cast(DataType::Int64) / lit(1000i64),
+                    EpochUnit::Ns => time.cast(DataType::Int64) / lit(1_000_000i64),
+                };
+                millis.cast(time_dtype)
+            }
+            DataType::Datetime(_, Some(_)) => time.cast(time_dtype),
+            DataType::Datetime(_, None) | DataType::Date => time
+                .cast(DataType::Datetime(TimeUnit::Milliseconds, None))
+                .dt()
+                .replace_time_zone(time_tz, lit("raise"), NonExistent::Raise),
+            DataType::String => time.str().to_datetime(
+                Some(TimeUnit::Milliseconds),
+                time_tz,
+                StrptimeOptions {
+                    format: Some(self.time_format.as_str().into()),
+                    ..Default::default()
+                },
+                lit("raise"),
+            ),
+            dtype => bail!("K线时间列类型不支持: 列={}, 类型={dtype}", self.time),
+        };
+
+        let close = column(&self.close)?.cast(DataType::Float64);
+        let size = column(&self.size)?.cast(DataType::Float64);
+        Ok(df
+            .select([
+                time.alias("time"),
+                column(&self.open)?.cast(DataType::Float64).alias("open"),
+                column(&self.high)?.cast(DataType::Float64).alias("high"),
+                column(&self.low)?.cast(DataType::Float64).alias("low"),
+                close.clone().alias("close"),
+                size.clone().alias("size"),
+                optional(&self.cash, close * size)
+                    .cast(DataType::Float64)
+                    .alias("cash"),
+                optional(&self.trades, lit(0i64))
+                    .cast(DataType::Int64)
+                    .alias("trades"),
+                optional(&self.taker_size, lit(0.0))
+                    .cast(DataType::Float64)
+                    .alias("taker_size"),
+                optional(&self.taker_cash, lit(0.0))
+                    .cast(DataType::Float64)
+                    .alias("taker_cash"),
+            ])
+            .sort(["time"], Default::default()))
+    }
+}
+
+/// 数据文件格式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Display)]
+pub enum FileFormat {
+    Csv,
+    Parquet,
+}
+
+impl FileFormat {
+    /// 由文件扩展名识别格式
+    pub fn from_path(path: &str) -> Result<Self> {
+        let ext = Path::new(path)
+            .extension()
+            .map(|ext| ext.to_string_lossy().to_lowercase())
+            .unwrap_or_default();
+        match ext.as_str() {
+            "csv" => Ok(Self::Csv),
+            "parquet" | "pq" => Ok(Self::Parquet),
+            _ => Err(anyhow!("不支持的数据文件格式: {path}")),
+        }
+    }
+}
+
+/// 本地 CSV / Parquet 文件
+pub struct FileSource {
+    /// 文件路径, {code} 替换为交易对
+    pub path: String,
+    /// 文件格式
+    pub format: FileFormat,
+    /// 数据K线周期
+    pub interval: String,
+    /// 列映射
+    pub columns: ColumnMapping,
+    /// 交易对及资金费率
+    pub metadata: Metadata,
+}
+
+impl FileSource {
+    pub fn new(path: &str, interval: &str, columns: ColumnMapping) -> Result<Self> {
+        interval_from_str(interval)?;
+        Ok(Self {
+            path: path.to_string(),
+            format: FileFormat::from_path(path)?,
+            interval: interval.to_string(),
+            columns,
+            metadata: Default::default(),
+        })
+    }
+
+    /// 交易对的数据文件路径
+    pub fn code_path(&self, code: &str) -> String {
+        self.path.replace("{code}", code)
+    }
+}
+
+impl DataSource for FileSource {
+    #[tracing::instrument(skip_all)]
+    fn load(
+        &self,
+        code: &str,
+        start_time: Time,
+        end_time: Time,
+        interval: &str,
+        history_bar_len: usize,
+    ) -> Result<DataFrame> {
+        let path = self.code_path(code);
+        ensure!(
+            Path::new(&path).exists(),
+            "缺失数据: 交易对={code}, 文件={path}"
+        );
+
+        let df = match self.format {
+            FileFormat::Csv => LazyCsvReader::new(PlPath::new(&path))
+                .with_has_header(true)
+                .finish()?,
+            FileFormat::Parquet => LazyFrame::scan_parquet(PlPath::new(&path), Default::default())?,
+        };
+
+        bars::align(
+            code,
+            self.columns.apply(df)?,
+            bars::need_resample(code, &self.interval, interval)?,
+            start_time,
+            end_time,
+            interval,
+            history_bar_len,
+        )
+    }
+
+    fn symbol(&self, code: &str) -> Result<Option<Symbol>> {
+        Ok(self.metadata.symbol(code))
+    }
+
+    fn funding_rates(
+        &self,
+        code: &str,
+        end_time: Time,
+    ) -> Result<Option<VecDeque<(Time, Decimal)>>> {
+        Ok(self.metadata.funding_rates(code, end_time))
+    }
+}
+
+/// 内存 DataFrame (如合成数据)
+pub struct DataFrameSource {
+    /// 各交易对K线
+    pub bars: Map<String, DataFrame>,
+    /// 数据K线周期
+    pub interval: String,
+    /// 列映射
+    pub columns: ColumnMapping,
+    /// 交易对及资金费率
+    pub metadata: Metadata,
+}
+
+impl DataFrameSource {
+    pub fn new(interval: &str, columns: ColumnMapping) -> Result<Self> {
+        interval_from_str(interval)?;
+        Ok(Self {
+            bars: Default::default(),
+            interval: interval.to_string(),
+            columns,
+            metadata: Default::default(),
+        })
+    }
+
+    /// 添加交易对K线
+    pub fn insert(&mut self, code: &str, df: DataFrame) {
+        self.bars.insert(code.to_string(), df);
+    }
+}
+
+impl DataSource for DataFrameSource {
+    #[tracing::instrument(skip_all)]
+    fn load(
+        &self,
+        code: &str,
+        start_time: Time,
+        end_time: Time,
+        interval: &str,
+        history_bar_len: usize,
+    ) -> Result<DataFrame> {
+        let df = self
+            .bars
+            .get(code)
+            .ok_or_else(|| anyhow!("缺失数据: 交易对={code}"))?;
+
+        bars::align(
+            code,
+            self.columns.apply(df.clone().lazy())?,
+            bars::need_resample(code, &self.interval, interval)?,
+            start_time,
+            end_time,
+            interval,
+            history_bar_len,
+        )
+    }
+
+    fn symbol(&self, code: &str) -> Result<Option<Symbol>> {
+        Ok(self.metadata.symbol(code))
+    }
+
+    fn funding_rates(
+        &self,
+        code: &str,
+        end_time: Time,
+    ) -> Result<Option<VecDeque<(Time, Decimal)>>> {
+        Ok(self.metadata.funding_rates(code, end_time))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 开始时间 2024-01-01 08:00:00 (Asia/Shanghai)
+    const START_MS: i64 = 1_704_067_200_000;
+
+    fn time(minutes: i64) -> Time {
+        time_from_millis(START_MS + minutes * 60_000).unwrap()
+    }
+
+    fn f64_values(df: &DataFrame, name: &str) -> Vec<f64> {
+        df.column(name)
+            .unwrap()
+            .f64()
+            .unwrap()
+            .into_no_null_iter()
+            .collect()
+    }
+
+    #[test]
+    fn test_column_mapping() {
+        // 秒级时间戳, 自定义列名, 无成交额等可选列
+        let df = df!(
+            "ts" => [START_MS / 1000 + 60, START_MS / 1000],
+            "o" => [2.0, 1.0],
+            "h" => [2.5, 1.5],
+            "l" => [1.5, 0.5],
+            "c" => [2.2, 1.2],
+            "v" => [20.0, 10.0],
+        )
+        .unwrap();
+        let columns = ColumnMapping {
+            time: "ts".into(),
+            time_unit: EpochUnit::S,
+            open: "o".into(),
+            high: "h".into(),
+            low: "l".into(),
+            close: "c".into(),
+            size: "v".into(),
+            ..Default::default()
+        };
+        let mut source = DataFrameSource::new("1m", columns).unwrap();
+        source.insert("BTC", df);
+
+        // 按时间排序, 成交额按收盘价 × 成交量估算, 成交笔数记为 0
+        let bars = source.load("BTC", time(0), time(1), "1m", 0).unwrap();
+        assert_eq!(f64_values(&bars, "open"), [1.0, 2.0]);
+        assert_eq!(f64_values(&bars, "cash"), [1.2 * 10.0, 2.2 * 20.0]);
+        assert_eq!(f64_values(&bars, "taker_size"), [0.0, 0.0]);
+        assert_eq!(bars.column("trades").unwrap().i64().unwrap().sum(), Some(0));
+
+        // 回测区间内缺失K线时报错, 交易对元数据未提供时为 None
+        assert!(source.load("BTC", time(0), time(2), "1m", 0).is_err());
+        assert!(source.symbol("BTC").unwrap().is_none());
+    }
+
+    #[test]
+    fn test_resample() {
+        let len = 10;
+        let df = df!(
+            "time" => (0..len).map(|i| START_MS + i * 60_000).collect::<Vec<i64>>(),
+            "open" => (0..len).map(|i| 100.0 + i as f64).collect::<Vec<f64>>(),
+            "high" => (0..len).map(|i| 110.0 + i as f64).collect::<Vec<f64>>(),
+            "low" => (0..len).map(|i| 90.0 + i as f64).collect::<Vec<f64>>(),
+            "close" => (0..len).map(|i| 105.0 + i as f64).collect::<Vec<f64>>(),
+            "size" => vec![1.0; len as usize],
+        )
+        .unwrap();
+        let mut source = DataFrameSource::new("1m", ColumnMapping::default()).unwrap();
+        source.insert("BTC", df);
+
+        // 1分钟K线重采样为5分钟K线: 开盘取首根, 最高/最低取极值, 收盘取末根, 成交量求和
+        let bars = source.load("BTC", time(0), time(5), "5m", 0).unwrap();
+        assert_eq!(f64_values(&bars, "open"), [100.0, 105.0]);
+        assert_eq!(f64_values(&bars, "high"), [114.0, 119.0]);
+        assert_eq!(f64_values(&bars, "low"), [90.0, 95.0]);
+        assert_eq!(f64_values(&bars, "close"), [109.0, 114.0]);
+        assert_eq!(f64_values(&bars, "size"), [5.0, 5.0]);
+
+        // 回测周期须为数据周期的整数倍
+        assert!(source.load("BTC", time(0), time(5), "30s", 0).is_err());
+    }
+
+    #[test]
+    fn test_file_source_csv() {
+        let dir = std::env::temp_dir();
+        let path = dir.join(format!("fuxi-quant-{}-{{code}}.csv", std::process::id()));
+        let path = path.to_string_lossy().to_string();
+        let csv = "datetime,open,high,low,close,volume\n\
+                   2024-01-01 08:01:00,2,2.5,1.5,2.2,20\n\
+                   2024-01-01 08:00:00,1,1.5,0.5,1.2,10\n";
+        let columns = ColumnMapping {
+            time: "datetime".into(),
+            size: "volume".into(),
+            ..Default::default()
+        };
+        let source = FileSource::new(&path, "1m", columns).unwrap();
+        std::fs::write(source.code_path("BTC"), csv).unwrap();
+
+        // 字符串时间按 Asia/Shanghai 解析
+        let bars = source.load("BTC", time(0), time(1), "1m", 0).unwrap();
+        assert_eq!(f64_values(&bars, "open"), [1.0, 2.0]);
+        assert_eq!(f64_values(&bars, "size"), [10.0, 20.0]);
+        assert!(source.load("ETH", time(0), time(1), "1m", 0).is_err());
+
+        std::fs::remove_file(source.code_path("BTC")).unwrap();
+    }
+}
@@ -0,0 +1,294 @@
+use crate::types::*;
+use anyhow::{Result, anyhow, bail, ensure};
+use rust_decimal::prelude::*;
+use serde::{Deserialize, de::DeserializeOwned};
+use std::path::{Path, PathBuf};
+
+/// 交易对元数据文件名(按顺序查找)
+pub const SYMBOLS_FILES: [&str; 3] = ["symbols.json", "symbols.yaml", "symbols.yml"];
+
+/// 杠杆分层文件名(leverageBracket 接口格式, 按顺序查找)
+pub const BRACKETS_FILES: [&str; 3] = ["brackets.json", "brackets.yaml", "brackets.yml"];
+
+/// 交易所信息(exchangeInfo 格式)
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ExchangeInfo {
+    symbols: Vec<SymbolInfo>,
+}
+
+/// 交易对信息
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct SymbolInfo {
+    /// 交易所交易对, 如: BTCUSDT
+    symbol: String,
+    /// 标的交易对(币本位合约的杠杆分层按此查找), 如: BTCUSD
+    #[serde(default)]
+    pair: Option<String>,
+    /// 合约面值(币本位合约)
+    #[serde(default)]
+    contract_size: Option<Decimal>,
+    /// 标的币种, 如: BTC
+    #[serde(default)]
+    base_asset: Option<String>,
+    /// 保证金币种(币本位合约与标的币种相同)
+    #[serde(default)]
+    margin_asset: Option<String>,
+    /// 上线时间(毫秒)
+    #[serde(default)]
+    onboard_date: Option<i64>,
+    /// 交易规则
+    #[serde(default)]
+    filters: Vec<SymbolFilter>,
+    /// 最大杠杆倍数
+    #[serde(default)]
+    max_leverage: Option<Decimal>,
+    /// 初始保证金率(百分比)
+    #[serde(default)]
+    required_margin_percent: Option<Decimal>,
+    /// 维持保证金率(百分比)
+    #[serde(default)]
+    maint_margin_percent: Option<Decimal>,
+    /// 杠杆分层(leverageBracket 格式)
+    #[serde(default)]
+    brackets: Vec<Bracket>,
+}
+
+/// 交易规则
+#[derive(Debug, Deserialize)]
+#[serde(tag = "filterType", rename_all = "SCREAMING_SNAKE_CASE")]
+enum SymbolFilter {
+    #[serde(rename_all = "camelCase")]
+    PriceFilter { tick_size: Decimal },
+    #[serde(rename_all = "camelCase")]
+    LotSize {
+        step_size: Decimal,
+        min_qty: Decimal,
+    },
+    #[serde(rename_all = "camelCase")]
+    MinNotional { notional: Decimal },
+    #[serde(other)]
+    Other,
+}
+
+/// 交易对杠杆分层(leverageBracket 格式, 币本位合约按标的交易对 pair 提供)
+#[derive(Debug, Deserialize)]
+struct SymbolBrackets {
+    #[serde(alias = "pair")]
+    symbol: String,
+    brackets: Vec<Bracket>,
+}
+
+/// 杠杆分层
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Bracket {
+    /// 最大杠杆倍数
+    initial_leverage: Decimal,
+    /// 名义价值上限(币本位合约为 qtyCap, 以币计价)
+    #[serde(alias = "qtyCap")]
+    notional_cap: Decimal,
+    /// 维持保证金率
+    maint_margin_ratio: Decimal,
+    /// 维持保证金速算额
+    #[serde(default)]
+    cum: Decimal,
+}
+
+/// 交易对元数据
+pub struct SymbolStore {
+    /// 元数据文件路径
+    path: PathBuf,
+    /// 市场
+    market: Market,
+    /// 交易所交易对 -> 交易对信息
+    symbols: Map<String, SymbolInfo>,
+}
+
+impl SymbolStore {
+    /// 从市场数据目录({data_dir}/{market})加载交易对元数据(JSON/YAML)
+    pub fn load(data_dir: &str, market: Market) -> Result<Self> {
+        let dir = market.dir(data_dir);
+        let path = SYMBOLS_FILES
+            .iter()
+            .map(|name| dir.join(name))
+            .find(|path| path.exists())
+            .ok_or_else(|| {
+                anyhow!(
+                    "交易对元数据文件不存在: 目录={}, 文件={}",
+                    dir.display(),
+                    SYMBOLS_FILES.join("/")
+                )
+            })?;
+        Self::open(&path, market)
+    }
+
+    /// 加载交易对元数据文件(JSON/YAML), 同目录的杠杆分层文件存在时补充元数据中缺少的杠杆分层
+    #[tracing::instrument(skip_all)]
+    pub fn open(path: &Path, market: Market) -> Result<Self> {
+        ensure!(
+            path.exists(),
+            "交易对元数据文件不存在: 文件={}",
+            path.display()
+        );
+        let path = path.to_path_buf();
+        let info: ExchangeInfo = read_file(&path)?;
+
+        let dir = path.parent().unwrap_or(Path::new(""));
+        let mut brackets = Map::new();
+        if let Some(path) = BRACKETS_FILES
+            .iter()
+            .map(|name| dir.join(name))
+            .find(|path| path.exists())
+        {
+            let items: Vec<SymbolBrackets> = read_file(&path)?;
+            brackets.extend(items.into_iter().map(|item| (item.symbol, item.brackets)));
+        }
+
+        let symbols = info
+            .symbols
+            .into_iter()
+            .map(|mut info| {
+                if info.brackets.is_empty()
+                    && let Some(items) = brackets
+                        .get(&info.symbol)
+                        .or_else(|| info.pair.as_ref().and_then(|pair| brackets.get(pair)))
+                {
+                    info.brackets = items.clone();
+                }
+                (info.symbol.clone(), info)
+            })
+            .collect();
+
+        Ok(Self {
+            path,
+            market,
+            symbols,
+        })
+    }
+
+    /// 查找交易对信息, 支持交易所交易对(BTCUSDT/BTCUSD_PERP)或币种(BTC, 按市场补全交易所交易对)
+    fn find(&self, code: &str) -> Option<&SymbolInfo> {
+        self.symbols
+            .get(code)
+            .or_else(|| self.symbols.get(&self.market.symbol(code)))
+    }
+
+    /// 元数据中是否有该交易对
+    pub fn contains(&self, code: &str) -> bool {
+        self.find(code).is_some()
+    }
+
+    /// 获取交易对上线时间(毫秒), 元数据未提供时返回 None
+    pub fn listing_time(&self, code: &str) -> Option<i64> {
+        self.find(code).and_then(|info| info.onboard_date)
+    }
+
+    /// 获取交易对, 支持交易所交易对(BTCUSDT/BTCUSD_PERP)或币种(BTC)
+    pub fn get(&self, code: &str) -> Result<Symbol> {
+        let info = self.find(code).ok_or_else(|| {
+            anyhow!(
+                "交易对元数据缺失: 交易对={code}, 文件={}",
+                self.path.display()
+            )
+        })?;
+
+        let mut symbol = Symbol::new(code);
+        let mut has_price_filter = false;
+        let mut has_lot_size = false;
+
+        for filter in &info.filters {
+            match filter {
+                SymbolFilter::PriceFilter { tick_size } => {
+                    symbol.price_tick = *tick_size;
+                    has_price_filter = true;
+                }
+                SymbolFilter::LotSize { step_size, min_qty } => {
+                    symbol.size_tick = *step_size;
+                    symbol.min_size = *min_qty;
+                    has_lot_size = true;
+                }
+                SymbolFilter::MinNotional { notional } => symbol.min_cash = *notional,
+                SymbolFilter::Other => {}
+            }
+        }
+
+        if !has_price_filter {
+            bail!("交易对元数据不完整: 交易对={code}, 缺少 PRICE_FILTER");
+        }
+        if !has_lot_size {
+            bail!("交易对元数据不完整: 交易对={code}, 缺少 LOT_SIZE");
+        }
+        ensure!(
+            symbol.price_tick > Decimal::ZERO && symbol.size_tick > Decimal::ZERO,
+            "交易对元数据无效: 交易对={code}, 价格/数量精度必须大于0"
+        );
+
+        if let Some(contract_size) = info.contract_size {
+            ensure!(
+                contract_size > Decimal::ZERO,
+                "交易对元数据无效: 交易对={code}, 合约面值必须大于0"
+            );
+            symbol.face_val = contract_size;
+        }
+        symbol.inverse = info.base_asset.is_some() && info.base_asset == info.margin_asset;
+
+        let mut tiers = info
+            .brackets
+            .iter()
+            .map(|bracket| MarginTier {
+                max_cash: bracket.notional_cap,
+                mmr: bracket.maint_margin_ratio,
+                mm_amount: bracket.cum,
+                max_lever: bracket.initial_leverage,
+            })
+            .collect::<Vec<MarginTier>>();
+        tiers.sort_by_key(|tier| tier.max_cash);
+
+        // 交易所信息接口不含杠杆分层, 缺失时按交易所信息中的保证金率使用单一分层
+        if tiers.is_empty() {
+            let positive = |value: Option<Decimal>| value.filter(|value| *value > Decimal::ZERO);
+            let mut tier = symbol.margin_tiers[0].clone();
+            if let Some(maint) = positive(info.maint_margin_percent) {
+                tier.mmr = maint / dec!(100);
+            }
+            if let Some(lever) = positive(info.max_leverage).or_else(|| {
+                positive(info.required_margin_percent)
+                    .map(|required| (dec!(100) / required).floor())
+            }) {
+                tier.max_lever = lever;
+            }
+            tracing::warn!(
+                "交易对缺少杠杆分层: 交易对={code}, 使用单一分层(维持保证金率={}, 最大杠杆={}), 可在元数据中提供 brackets, 或将 leverageBracket 接口返回的数据保存为 {}/{}",
+                tier.mmr,
+                tier.max_lever,
+                self.path.parent().unwrap_or(Path::new("")).display(),
+                BRACKETS_FILES[0]
+            );
+            tiers.push(tier);
+        }
+
+        symbol.max_lever = tiers
+            .iter()
+            .map(|tier| tier.max_lever)
+            .max()
+            .unwrap_or(symbol.max_lever);
+        symbol.margin_tiers = tiers;
+
+        Ok(symbol)
+    }
+}
+
+/// 读取 JSON/YAML 文件
+fn read_file<T: DeserializeOwned>(path: &Path) -> Result<T> {
+    let content = std::fs::read_to_string(path)?;
+    let is_json = path.extension().is_some_and(|ext| ext == "json");
+    if is_json {
+        serde_json::from_str(&content)
+            .map_err(|e| anyhow!("交易对元数据解析失败: 文件={}, {e}", path.display()))
+    } else {
+        serde_yml::from_str(&content)
+            .map_err(|e| anyhow!("交易对元数据解析失败: 文件={}, {e}", path.display()))
+    }
+}
@@ -1,10 +1,12 @@
+use crate::symbols::{BRACKETS_FILES, SYMBOLS_FILES};
 use anyhow::{Result, ensure};
-use chrono::DateTime;
+use chrono::{DateTime, Duration};
 use chrono_tz::Tz;
 use indexmap::IndexMap;
 use polars::prelude::*;
 use rust_decimal::prelude::*;
-use strum::Display;
+use std::{collections::VecDeque, path::PathBuf};
+use strum::{Display, EnumString};
 
 /// 时间
 pub type Time = DateTime<Tz>;
@@ -28,6 +30,33 @@ pub enum OrderType {
     Limit,
     /// 市价单
     Market,
+    /// 止损市价单
+    StopMarket,
+    /// 止损限价单
+    StopLimit,
+    /// 止盈市价单
+    TakeProfitMarket,
+    /// 止盈限价单
+    TakeProfitLimit,
+    /// 追踪止损单
+    TrailingStop,
+}
+
+impl OrderType {
+    /// 是否为条件单(需触发后才参与撮合)
+    #[inline]
+    pub fn is_trigger(&self) -> bool {
+        !matches!(self, OrderType::Limit | OrderType::Market)
+    }
+
+    /// 是否需要指定价格(限价单及触发后转为限价的条件单)
+    #[inline]
+    pub fn is_limit(&self) -> bool {
+        matches!(
+            self,
+            OrderType::Limit | OrderType::StopLimit | OrderType::TakeProfitLimit
+        )
+    }
 }
 
 /// 交易方向
@@ -39,6 +68,29 @@ pub enum Direction {
     Short,
 }
 
+/// 保证金模式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Display, Default)]
+pub enum MarginMode {
+    /// 全仓
+    #[default]
+    Cross,
+    /// 逐仓
+    Isolated,
+}
+
+/// 持仓模式
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Display, EnumString, Default,
+)]
+#[strum(serialize_all = "snake_case")]
+pub enum PositionMode {
+    /// 双向持仓
+    #[default]
+    Hedge,
+    /// 单向持仓
+    OneWay,
+}
+
 /// 买卖方向
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Display)]
 pub enum Side {
@@ -48,6 +100,26 @@ pub enum Side {
     Sell,
 }
 
+impl Side {
+    /// 开仓方向(买入做多, 卖出做空)
+    #[inline]
+    pub fn open_direction(&self) -> Direction {
+        match self {
+            Side::Buy => Direction::Long,
+            Side::Sell => Direction::Short,
+        }
+    }
+
+    /// 平仓方向(买入平空, 卖出平多)
+    #[inline]
+    pub fn close_direction(&self) -> Direction {
+        match self {
+            Side::Buy => Direction::Short,
+            Side::Sell => Direction::Long,
+        }
+    }
+}
+
 /// 订单状态
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Display)]
 pub enum OrderStatus {
@@ -55,6 +127,8 @@ pub enum OrderStatus {
     New,
     /// 挂单中
     Pending,
+    /// 部分成交
+    PartiallyFilled,
     /// 已成交
     Filled,
     /// 取消中
@@ -63,6 +137,103 @@ pub enum OrderStatus {
     Canceled,
     /// 已拒绝
     Rejected,
+    /// 等待激活(订单组中等待入场单成交)
+    Waiting,
+}
+
+/// 订单拒绝原因
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Display)]
+pub enum RejectReason {
+    /// 未知交易对
+    UnknownSymbol,
+    /// 保证金不足
+    InsufficientMargin,
+    /// 可平持仓不足
+    InsufficientPosition,
+    /// 低于最小交易数量
+    BelowMinSize,
+    /// 低于最小交易金额
+    BelowMinNotional,
+    /// 价格不符合最小价格变动
+    InvalidTick,
+    /// 只减仓订单会增加持仓或无持仓可减
+    ReduceOnly,
+    /// 只做挂单会立即成交
+    PostOnly,
+    /// 超过杠杆分层的最大杠杆
+    ExceedMaxLever,
+}
+
+/// 订单有效方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Display)]
+pub enum TimeInForce {
+    /// 成交为止
+    Gtc,
+    /// 立即成交并取消剩余
+    Ioc,
+    /// 全部成交或立即取消
+    Fok,
+}
+
+/// K线内价格路径模型
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Display, EnumString)]
+#[strum(serialize_all = "snake_case")]
+pub enum IntrabarPath {
+    /// 开-高-低-收
+    Ohlc,
+    /// 开-低-高-收
+    Olhc,
+    /// 先到达距离开盘价较近的极值
+    Nearest,
+    /// 对策略最不利: 净多头先到最低价, 净空头先到最高价
+    Worst,
+}
+
+/// 币安合约市场
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Display, EnumString, Default)]
+#[strum(serialize_all = "snake_case")]
+pub enum Market {
+    /// U本位合约(USDⓈ-M)
+    #[default]
+    Um,
+    /// 币本位合约(COIN-M)
+    Cm,
+}
+
+impl Market {
+    /// 交易所交易对, 如: BTCUSDT(U本位), BTCUSD_PERP(币本位)
+    pub fn symbol(&self, code: &str) -> String {
+        match self {
+            Market::Um => format!("{code}USDT"),
+            Market::Cm => format!("{code}USD_PERP"),
+        }
+    }
+
+    /// 市场数据目录: {data_dir}/{market}
+    pub fn dir(&self, data_dir: &str) -> PathBuf {
+        PathBuf::from(data_dir).join(self.to_string())
+    }
+
+    /// 将旧版本直接位于 {data_dir} 下的数据(K线、资金费率、归档及元数据文件)移动到市场数据目录,
+    /// 市场数据目录中已存在同名文件时保留旧文件
+    pub fn migrate_legacy_dir(&self, data_dir: &str) -> Result<()> {
+        let legacy_dir = PathBuf::from(data_dir);
+        let dir = self.dir(data_dir);
+        let names = ["bars", "funding", "resources"]
+            .into_iter()
+            .chain(SYMBOLS_FILES)
+            .chain(BRACKETS_FILES);
+        for name in names {
+            let (from, to) = (legacy_dir.join(name), dir.join(name));
+            if !from.exists() || to.exists() {
+                continue;
+            }
+            std::fs::create_dir_all(&dir)?;
+            std::fs::rename(&from, &to)?;
+            tracing::info!("迁移旧版本数据: {} -> {}", from.display(), to.display());
+        }
+        Ok(())
+    }
 }
 
 /// 定时器
@@ -97,6 +268,37 @@ pub struct Bar {
     pub cash: f64,
 }
 
+/// 秒级K线
+#[derive(Debug, Clone)]
+pub struct SubBars {
+    /// 时间(毫秒)
+    pub times: Vec<i64>,
+    /// 开盘价, 最高价, 最低价, 收盘价
+    pub prices: Vec<[f64; 4]>,
+}
+
+impl SubBars {
+    /// 时间范围 [start_ms, end_ms) 内的K线
+    pub fn range(&self, start_ms: i64, end_ms: i64) -> &[[f64; 4]] {
+        let start = self.times.partition_point(|t| *t < start_ms);
+        let end = self.times.partition_point(|t| *t < end_ms);
+        &self.prices[start..end]
+    }
+}
+
+/// 维持保证金阶梯
+#[derive(Debug, Clone)]
+pub struct MarginTier {
+    /// 名义价值上限
+    pub max_cash: Decimal,
+    /// 维持保证金率
+    pub mmr: Decimal,
+    /// 维持保证金速算额
+    pub mm_amount: Decimal,
+    /// 最大杠杆倍数
+    pub max_lever: Decimal,
+}
+
 /// 交易对
 #[derive(Debug, Clone)]
 pub struct Symbol {
@@ -112,14 +314,20 @@ pub struct Symbol {
     pub min_cash: Decimal,
     /// 最大杠杆倍数
     pub max_lever: Decimal,
-    /// 合约面值
+    /// 合约面值(每张合约对应的标的数量, 币本位合约为美元)
     pub face_val: Decimal,
+    /// 是否为币本位(反向)合约: 保证金、盈亏以标的币种计价
+    pub inverse: bool,
     /// 标记价格
     pub mark_price: Decimal,
     /// 最新成交价格
     pub price: Decimal,
     /// 资金费率
     pub funding_rate: Decimal,
+    /// 资金费结算间隔(小时)
+    pub funding_interval: u32,
+    /// 维持保证金阶梯(按名义价值上限升序)
+    pub margin_tiers: Vec<MarginTier>,
 }
 
 impl Symbol {
@@ -132,9 +340,17 @@ impl Symbol {
             min_cash: dec!(0.00000001),
             max_lever: dec!(10),
             face_val: dec!(1),
+            inverse: false,
             mark_price: dec!(0),
             price: dec!(0),
             funding_rate: dec!(0),
+            funding_interval: 8,
+            margin_tiers: vec![MarginTier {
+                max_cash: Decimal::MAX,
+                mmr: dec!(0.004),
+                mm_amount: dec!(0),
+                max_lever: dec!(10),
+            }],
         }
     }
 
@@ -148,9 +364,109 @@ impl Symbol {
         (price / self.price_tick).floor() * self.price_tick
     }
 
+    /// 价格按最小价格变动四舍五入
+    #[inline]
+    pub fn round_price(&self, price: Decimal) -> Decimal {
+        (price / self.price_tick).round() * self.price_tick
+    }
+
+    /// 价格是否为最小价格变动的整数倍
+    #[inline]
+    pub fn is_tick_price(&self, price: Decimal) -> bool {
+        self.price_tick.is_zero() || (price % self.price_tick).is_zero()
+    }
+
+    /// 成交价格按最小价格变动取整, 取对交易者不利的方向(买入向上, 卖出向下)
+    #[inline]
+    pub fn adverse_price(&self, price: Decimal, side: Side) -> Decimal {
+        match side {
+            Side::Buy => (price / self.price_tick).ceil() * self.price_tick,
+            Side::Sell => (price / self.price_tick).floor() * self.price_tick,
+        }
+    }
+
+    /// 金额换算为数量(U本位金额为计价币, 币本位金额为标的币)
     #[inline]
     pub fn cash_to_size(&self, cash: Decimal, price: Option<Decimal>) -> Decimal {
-        self.trunc_size(cash / price.unwrap_or(self.mark_price))
+        let price = price.unwrap_or(self.mark_price);
+        if self.inverse {
+            self.trunc_size(cash * price / self.face_val)
+        } else {
+            self.trunc_size(cash / (price * self.face_val))
+        }
+    }
+
+    /// 计算名义价值
+    ///
+    /// U本位: 价格 * 数量 * 面值; 币本位: 数量 * 面值 / 价格
+    #[inline]
+    pub fn calc_cash(&self, price: Decimal, size: Decimal) -> Decimal {
+        if !self.inverse {
+            price * size * self.face_val
+        } else if price.is_zero() {
+            Decimal::ZERO
+        } else {
+            size * self.face_val / price
+        }
+    }
+
+    /// 计算持仓盈亏
+    ///
+    /// U本位: (平仓价 - 开仓价) * 数量 * 面值; 币本位: 数量 * 面值 * (1 / 开仓价 - 1 / 平仓价)
+    #[inline]
+    pub fn calc_pnl(
+        &self,
+        direction: Direction,
+        entry: Decimal,
+        price: Decimal,
+        size: Decimal,
+    ) -> Decimal {
+        let pnl = if !self.inverse {
+            (price - entry) * size * self.face_val
+        } else if entry.is_zero() || price.is_zero() {
+            Decimal::ZERO
+        } else {
+            size * self.face_val * (Decimal::ONE / entry - Decimal::ONE / price)
+        };
+        match direction {
+            Direction::Long => pnl,
+            Direction::Short => -pnl,
+        }
+    }
+
+    /// 计算加仓后的持仓均价(U本位为算术加权, 币本位为调和加权)
+    #[inline]
+    pub fn calc_avg_price(
+        &self,
+        price: Decimal,
+        size: Decimal,
+        fill_price: Decimal,
+        fill_size: Decimal,
+    ) -> Decimal {
+        if size.is_zero() {
+            return fill_price;
+        }
+        let total_size = size + fill_size;
+        if self.inverse {
+            total_size / (size / price + fill_size / fill_price)
+        } else {
+            (price * size + fill_price * fill_size) / total_size
+        }
+    }
+
+    /// 获取名义价值对应的维持保证金阶梯
+    pub fn margin_tier(&self, cash: Decimal) -> Option<&MarginTier> {
+        self.margin_tiers
+            .iter()
+            .find(|tier| cash <= tier.max_cash)
+            .or(self.margin_tiers.last())
+    }
+
+    /// 计算名义价值对应的维持保证金
+    pub fn calc_maint_margin(&self, cash: Decimal) -> Decimal {
+        self.margin_tier(cash)
+            .map(|tier| (cash * tier.mmr - tier.mm_amount).max(Decimal::ZERO))
+            .unwrap_or_default()
     }
 }
 
@@ -173,8 +489,31 @@ pub struct Trade {
     pub size: Decimal,
     /// 手续费
     pub fee: Decimal,
+    /// 滑点成本
+    pub slippage: Decimal,
     /// 已实现盈亏
     pub rpl: Decimal,
+    /// 订单标签
+    pub tag: Option<String>,
+    /// 平仓成交对应的开仓标签及数量(先开先平)
+    pub entry_tags: Vec<(Option<String>, Decimal)>,
+}
+
+/// 资金费记录(用于回测)
+#[derive(Debug, Clone)]
+pub struct Funding {
+    /// 结算时间
+    pub time: Time,
+    /// 交易对
+    pub code: String,
+    /// 资金费率
+    pub rate: Decimal,
+    /// 结算价格
+    pub price: Decimal,
+    /// 净持仓数量(多头为正, 空头为负)
+    pub size: Decimal,
+    /// 资金费(正数为支出, 负数为收入)
+    pub cash: Decimal,
 }
 
 /// 订单
@@ -200,6 +539,90 @@ pub struct Order {
     pub status: OrderStatus,
     /// 创建时间
     pub time: Time,
+    /// 生效时间(下单延迟后)
+    pub active_time: Time,
+    /// 撤单生效时间(撤单延迟后)
+    pub cancel_time: Option<Time>,
+    /// 触发价格(条件单), 追踪止损单为激活价格
+    pub trigger_price: Option<Decimal>,
+    /// 回调比例(追踪止损单)
+    pub callback_rate: Option<Decimal>,
+    /// 追踪极值价格(追踪止损单, 未激活时为空)
+    pub trail_price: Option<Decimal>,
+    /// 是否已触发(条件单)
+    pub triggered: bool,
+    /// 有效方式
+    pub time_in_force: TimeInForce,
+    /// 只做挂单
+    pub post_only: bool,
+    /// 只减仓
+    pub reduce_only: bool,
+    /// 拒绝原因
+    pub reason: Option<RejectReason>,
+    /// 父订单id(括号单的止盈止损单, 入场单成交后激活)
+    pub parent_id: Option<String>,
+    /// OCO关联订单id(任一订单成交时撤销另一订单)
+    pub oco_id: Option<String>,
+    /// 订单标签
+    pub tag: Option<String>,
+}
+
+/// 下单请求
+#[derive(Debug, Clone)]
+pub struct OrderRequest {
+    /// 交易对
+    pub code: String,
+    /// 订单类型
+    pub type_: OrderType,
+    /// 交易方向
+    pub direction: Direction,
+    /// 买卖方向
+    pub side: Side,
+    /// 订单数量
+    pub size: Decimal,
+    /// 订单价格
+    pub price: Option<Decimal>,
+    /// 触发价格(条件单), 追踪止损单为激活价格
+    pub trigger_price: Option<Decimal>,
+    /// 回调比例(追踪止损单)
+    pub callback_rate: Option<Decimal>,
+    /// 有效方式
+    pub time_in_force: TimeInForce,
+    /// 只做挂单
+    pub post_only: bool,
+    /// 只减仓
+    pub reduce_only: bool,
+    /// 客户端订单id(为空时自动生成)
+    pub client_id: Option<String>,
+    /// 订单标签
+    pub tag: Option<String>,
+}
+
+impl OrderRequest {
+    pub fn new(
+        code: &str,
+        type_: OrderType,
+        direction: Direction,
+        side: Side,
+        size: Decimal,
+        price: Option<Decimal>,
+    ) -> Self {
+        Self {
+            code: code.to_string(),
+            type_,
+            direction,
+            side,
+            size,
+            price,
+            trigger_price: None,
+            callback_rate: None,
+            time_in_force: TimeInForce::Gtc,
+            post_only: false,
+            reduce_only: false,
+            client_id: None,
+            tag: None,
+        }
+    }
 }
 
 /// 方向持仓
@@ -209,6 +632,40 @@ pub struct DirectionPosition {
     pub price: Decimal,
     /// 持仓数量
     pub size: Decimal,
+    /// 强平价格
+    pub liq_price: Decimal,
+    /// 逐仓保证金
+    pub margin: Decimal,
+    /// 按开仓标签划分的持仓数量(先开先平)
+    pub entry_tags: Vec<(Option<String>, Decimal)>,
+}
+
+impl DirectionPosition {
+    /// 记录开仓数量的标签, 与最近一笔开仓标签相同时合并
+    pub(crate) fn open_tag(&mut self, tag: &Option<String>, size: Decimal) {
+        match self.entry_tags.last_mut() {
+            Some((last, last_size)) if last == tag => *last_size += size,
+            _ => self.entry_tags.push((tag.clone(), size)),
+        }
+    }
+
+    /// 按先开先平取出平仓数量对应的开仓标签
+    pub(crate) fn close_tags(&mut self, size: Decimal) -> Vec<(Option<String>, Decimal)> {
+        let mut closed = Vec::new();
+        let mut remain = size;
+        while remain > Decimal::ZERO
+            && let Some((tag, lot_size)) = self.entry_tags.first_mut()
+        {
+            let size = remain.min(*lot_size);
+            closed.push((tag.clone(), size));
+            *lot_size -= size;
+            remain -= size;
+            if lot_size.is_zero() {
+                self.entry_tags.remove(0);
+            }
+        }
+        closed
+    }
 }
 
 /// 持仓
@@ -218,6 +675,8 @@ pub struct Position {
     pub code: String,
     /// 杠杆倍数
     pub lever: Decimal,
+    /// 保证金模式
+    pub margin_mode: MarginMode,
     /// 多头持仓
     pub long: DirectionPosition,
     /// 空头持仓
@@ -229,10 +688,35 @@ impl Position {
         Self {
             code: code.to_string(),
             lever: dec!(1),
+            margin_mode: MarginMode::Cross,
             long: Default::default(),
             short: Default::default(),
         }
     }
+
+    /// 是否逐仓
+    #[inline]
+    pub fn is_isolated(&self) -> bool {
+        self.margin_mode == MarginMode::Isolated
+    }
+
+    /// 获取方向持仓
+    #[inline]
+    pub fn get(&self, direction: Direction) -> &DirectionPosition {
+        match direction {
+            Direction::Long => &self.long,
+            Direction::Short => &self.short,
+        }
+    }
+
+    /// 获取可变方向持仓
+    #[inline]
+    pub fn get_mut(&mut self, direction: Direction) -> &mut DirectionPosition {
+        match direction {
+            Direction::Long => &mut self.long,
+            Direction::Short => &mut self.short,
+        }
+    }
 }
 
 /// 上下文
@@ -250,6 +734,14 @@ pub struct Context {
     pub bars: Map<String, DataFrame>,
     /// 信号
     pub signals: DataFrame,
+    /// 持仓模式
+    pub position_mode: PositionMode,
+    /// 历史订单(已完成, 按完成顺序)
+    pub history_orders: VecDeque<Order>,
+    /// 成交记录
+    pub trades: VecDeque<Trade>,
+    /// 历史订单及成交记录保留数量
+    pub history_len: usize,
 }
 
 impl Context {
@@ -272,50 +764,127 @@ impl Context {
             orders: Default::default(),
             bars,
             signals: Default::default(),
+            position_mode: Default::default(),
+            history_orders: Default::default(),
+            trades: Default::default(),
+            history_len: 1000,
+        })
+    }
+
+    /// 将已完成订单移入历史订单, 超出保留数量时移除最早的记录
+    pub fn archive_order(&mut self, id: &str) {
+        let Some(order) = self.orders.swap_remove(id) else {
+            return;
+        };
+        if self.history_len == 0 {
+            return;
+        }
+        if self.history_orders.len() >= self.history_len {
+            self.history_orders.pop_front();
+        }
+        self.history_orders.push_back(order);
+    }
+
+    /// 记录成交, 超出保留数量时移除最早的记录
+    pub fn add_trade(&mut self, trade: Trade) {
+        if self.history_len == 0 {
+            return;
+        }
+        if self.trades.len() >= self.history_len {
+            self.trades.pop_front();
+        }
+        self.trades.push_back(trade);
+    }
+
+    /// 查询订单(含历史订单)
+    pub fn find_order(&self, id: &str) -> Option<&Order> {
+        self.orders.get(id).or_else(|| {
+            self.history_orders
+                .iter()
+                .rev()
+                .find(|order| order.id == id)
         })
     }
 
+    /// 是否单向持仓
+    #[inline]
+    pub fn is_one_way(&self) -> bool {
+        self.position_mode == PositionMode::OneWay
+    }
+
+    /// 单向持仓模式下订单的持仓方向: 有反向持仓时平仓, 否则开仓
+    pub fn net_direction(&self, code: &str, side: Side) -> Direction {
+        let pos = self.positions.get(code).unwrap();
+        if pos.get(side.close_direction()).size > Decimal::ZERO {
+            side.close_direction()
+        } else {
+            side.open_direction()
+        }
+    }
+
     /// 计算未成交订单冻结资金
     pub fn calc_order_frozen_cash(&self) -> Decimal {
         self.orders
             .values()
             .filter(|order| {
+                matches!(
+                    order.status,
+                    OrderStatus::New
+                        | OrderStatus::Pending
+                        | OrderStatus::PartiallyFilled
+                        | OrderStatus::Canceling
+                ) && order.size > order.filled
+            })
+            .map(|order| {
                 let is_open = matches!(
                     (order.direction, order.side),
                     (Direction::Long, Side::Buy) | (Direction::Short, Side::Sell)
                 );
-                is_open
-                    && matches!(
-                        order.status,
-                        OrderStatus::New | OrderStatus::Pending | OrderStatus::Canceling
-                    )
-                    && order.size > order.filled
-            })
-            .map(|order| {
-                let unfill_size = order.size - order.filled;
                 let pos = self.positions.get(&order.code).unwrap();
-                let price = if order.type_ == OrderType::Market {
-                    self.symbols.get(&order.code).unwrap().mark_price
+                let unfill_size = if is_open {
+                    order.size - order.filled
+                } else if self.is_one_way() {
+                    // 单向持仓: 平仓单超出持仓的部分反向开仓
+                    (order.size - order.filled - pos.get(order.direction).size).max(Decimal::ZERO)
                 } else {
-                    order.price.unwrap()
+                    Decimal::ZERO
                 };
-                (price * unfill_size) / pos.lever
+                let symbol = self.symbols.get(&order.code).unwrap();
+                let price = order
+                    .price
+                    .or(order.trigger_price)
+                    .unwrap_or(symbol.mark_price);
+                symbol.calc_cash(price, unfill_size) / pos.lever
             })
             .sum()
     }
 
-    /// 计算持仓冻结资金
+    /// 计算持仓冻结资金(逐仓持仓冻结其保证金及未实现盈亏)
     pub fn calc_pos_frozen_cash(&self) -> Decimal {
         self.positions
             .iter()
             .map(|(code, pos)| {
+                if pos.is_isolated() {
+                    return pos.long.margin
+                        + pos.short.margin
+                        + self.calc_pos_upl(code, Direction::Long)
+                        + self.calc_pos_upl(code, Direction::Short);
+                }
                 let symbol = self.symbols.get(code).unwrap();
-                (symbol.mark_price * pos.long.size) / pos.lever
-                    + (symbol.mark_price * pos.short.size) / pos.lever
+                symbol.calc_cash(symbol.mark_price, pos.long.size) / pos.lever
+                    + symbol.calc_cash(symbol.mark_price, pos.short.size) / pos.lever
             })
             .sum()
     }
 
+    /// 计算逐仓保证金
+    pub fn calc_isolated_margin(&self) -> Decimal {
+        self.positions
+            .values()
+            .map(|pos| pos.long.margin + pos.short.margin)
+            .sum()
+    }
+
     /// 计算冻结资金
     pub fn calc_frozen_cash(&self) -> Decimal {
         self.calc_order_frozen_cash() + self.calc_pos_frozen_cash()
@@ -330,17 +899,136 @@ impl Context {
     pub fn calc_upl(&self) -> Decimal {
         self.positions
             .iter()
-            .map(|(code, pos)| {
-                let symbol = self.symbols.get(code).unwrap();
-                (symbol.mark_price - pos.long.price) * pos.long.size
-                    + (pos.short.price - symbol.mark_price) * pos.short.size
+            .map(|(code, _)| {
+                self.calc_pos_upl(code, Direction::Long) + self.calc_pos_upl(code, Direction::Short)
             })
             .sum()
     }
 
     /// 计算总权益
     pub fn calc_equity(&self) -> Decimal {
-        self.cash + self.calc_upl()
+        self.cash + self.calc_isolated_margin() + self.calc_upl()
+    }
+
+    /// 计算全仓权益(不含逐仓保证金及逐仓未实现盈亏)
+    pub fn calc_cross_equity(&self) -> Decimal {
+        let cross_upl = self
+            .positions
+            .iter()
+            .filter(|(_, pos)| !pos.is_isolated())
+            .map(|(code, _)| {
+                self.calc_pos_upl(code, Direction::Long) + self.calc_pos_upl(code, Direction::Short)
+            })
+            .sum::<Decimal>();
+        self.cash + cross_upl
+    }
+
+    /// 计算方向持仓的未实现盈亏
+    pub fn calc_pos_upl(&self, code: &str, direction: Direction) -> Decimal {
+        let symbol = self.symbols.get(code).unwrap();
+        let pos = self.positions.get(code).unwrap();
+        let dir_pos = pos.get(direction);
+        symbol.calc_pnl(direction, dir_pos.price, symbol.mark_price, dir_pos.size)
+    }
+
+    /// 计算方向持仓的维持保证金
+    pub fn calc_pos_maint_margin(&self, code: &str, direction: Direction) -> Decimal {
+        let symbol = self.symbols.get(code).unwrap();
+        let pos = self.positions.get(code).unwrap();
+        let size = match direction {
+            Direction::Long => pos.long.size,
+            Direction::Short => pos.short.size,
+        };
+        symbol.calc_maint_margin(symbol.calc_cash(symbol.mark_price, size))
+    }
+
+    /// 计算维持保证金
+    pub fn calc_maint_margin(&self) -> Decimal {
+        self.positions
+            .keys()
+            .map(|code| {
+                self.calc_pos_maint_margin(code, Direction::Long)
+                    + self.calc_pos_maint_margin(code, Direction::Short)
+            })
+            .sum()
+    }
+
+    /// 计算方向持仓的强平价格
+    ///
+    /// 全仓以全仓权益承担亏损(其他全仓持仓按标记价格计算), 逐仓仅以该持仓保证金承担亏损。
+    pub fn calc_liq_price(&self, code: &str, direction: Direction) -> Decimal {
+        let symbol = self.symbols.get(code).unwrap();
+        let pos = self.positions.get(code).unwrap();
+        let dir_pos = match direction {
+            Direction::Long => &pos.long,
+            Direction::Short => &pos.short,
+        };
+
+        if dir_pos.size.is_zero() {
+            return Decimal::ZERO;
+        }
+
+        let Some(tier) = symbol.margin_tier(symbol.calc_cash(symbol.mark_price, dir_pos.size))
+        else {
+            return Decimal::ZERO;
+        };
+
+        // 除该方向持仓外可承担亏损的资金
+        let collateral = if pos.is_isolated() {
+            dir_pos.margin
+        } else {
+            let cross_maint_margin = self
+                .positions
+                .iter()
+                .filter(|(_, pos)| !pos.is_isolated())
+                .map(|(code, _)| {
+                    self.calc_pos_maint_margin(code, Direction::Long)
+                        + self.calc_pos_maint_margin(code, Direction::Short)
+                })
+                .sum::<Decimal>();
+            self.calc_cross_equity()
+                - self.calc_pos_upl(code, direction)
+                - (cross_maint_margin - self.calc_pos_maint_margin(code, direction))
+        };
+
+        // U本位(Q = size * face_val):
+        // 多头: collateral + (P - entry) * Q = P * Q * mmr - mm_amount
+        // 空头: collateral + (entry - P) * Q = P * Q * mmr - mm_amount
+        // 币本位(以币计价):
+        // 多头: collateral + Q * (1 / entry - 1 / P) = Q / P * mmr - mm_amount
+        // 空头: collateral + Q * (1 / P - 1 / entry) = Q / P * mmr - mm_amount
+        let qty = dir_pos.size * symbol.face_val;
+        let price = if symbol.inverse {
+            let base = qty / dir_pos.price;
+            let (numer, denom) = match direction {
+                Direction::Long => (
+                    qty * (Decimal::ONE + tier.mmr),
+                    collateral + base + tier.mm_amount,
+                ),
+                Direction::Short => (
+                    qty * (Decimal::ONE - tier.mmr),
+                    base - collateral - tier.mm_amount,
+                ),
+            };
+            if denom <= Decimal::ZERO {
+                // 多头任意价格均低于维持保证金(立即强平), 空头任意价格均不触及强平
+                return Decimal::MAX;
+            }
+            numer / denom
+        } else {
+            match direction {
+                Direction::Long => {
+                    (dir_pos.price * qty - collateral - tier.mm_amount)
+                        / (qty * (Decimal::ONE - tier.mmr))
+                }
+                Direction::Short => {
+                    (dir_pos.price * qty + collateral + tier.mm_amount)
+                        / (qty * (Decimal::ONE + tier.mmr))
+                }
+            }
+        };
+
+        price.max(Decimal::ZERO)
     }
 
     /// 计算持仓冻结数量
@@ -357,7 +1045,10 @@ impl Context {
                     && order.direction == direction
                     && matches!(
                         order.status,
-                        OrderStatus::New | OrderStatus::Pending | OrderStatus::Canceling
+                        OrderStatus::New
+                            | OrderStatus::Pending
+                            | OrderStatus::PartiallyFilled
+                            | OrderStatus::Canceling
                     )
             })
             .map(|order| order.size - order.filled)
@@ -384,13 +1075,24 @@ pub trait Engine {
     fn get_time(&self) -> Time;
     /// 获取k线
     fn get_bars(&self, code: &str, all: bool) -> Result<DataFrame>;
+    /// 获取指定周期的k线(仅包含已收盘的k线)
+    fn get_interval_bars(&self, code: &str, interval: &str, n: usize) -> Result<DataFrame>;
     /// 获取信号
     fn get_signals(&self) -> DataFrame;
     /// 设置信号
     fn set_signals(&mut self, signals: DataFrame) -> Result<()>;
     /// 设置杠杆
     fn set_lever(&mut self, code: &str, lever: u32) -> Result<()>;
+    /// 设置保证金模式(需无持仓及未完成订单)
+    fn set_margin_mode(&mut self, code: &str, mode: MarginMode) -> Result<()>;
+    /// 追加逐仓保证金
+    fn add_margin(&mut self, code: &str, direction: Direction, cash: Decimal) -> Result<()>;
+    /// 减少逐仓保证金
+    fn remove_margin(&mut self, code: &str, direction: Direction, cash: Decimal) -> Result<()>;
+    /// 提交下单请求
+    fn submit_order(&mut self, req: OrderRequest) -> Result<String>;
     /// 下单
+    #[inline]
     fn place_order(
         &mut self,
         code: &str,
@@ -399,9 +1101,68 @@ pub trait Engine {
         side: Side,
         size: Decimal,
         price: Option<Decimal>,
-    ) -> Result<String>;
+    ) -> Result<String> {
+        self.submit_order(OrderRequest::new(code, type_, direction, side, size, price))
+    }
+    /// 下条件单
+    #[inline]
+    fn place_trigger_order(
+        &mut self,
+        code: &str,
+        type_: OrderType,
+        direction: Direction,
+        side: Side,
+        size: Decimal,
+        price: Option<Decimal>,
+        trigger_price: Option<Decimal>,
+        callback_rate: Option<Decimal>,
+    ) -> Result<String> {
+        let mut req = OrderRequest::new(code, type_, direction, side, size, price);
+        req.trigger_price = trigger_price;
+        req.callback_rate = callback_rate;
+        self.submit_order(req)
+    }
     /// 撤单
     fn cancel_order(&mut self, id: &str) -> Result<()>;
+    /// 改单(仅限价类订单), 价格不变且数量减少时保留排队优先级
+    fn amend_order(&mut self, id: &str, price: Decimal, size: Decimal) -> Result<()>;
+    /// 批量下单, 任一订单下单失败时全部撤回
+    fn place_orders(&mut self, reqs: Vec<OrderRequest>) -> Result<Vec<String>>;
+    /// 批量撤单, 任一订单无法撤销时全部不撤
+    fn cancel_orders(&mut self, ids: &[String]) -> Result<()>;
+    /// 下OCO订单: 任一订单成交时撤销另一订单
+    fn place_oco(&mut self, first: OrderRequest, second: OrderRequest) -> Result<Vec<String>>;
+    /// 下括号单: 入场单成交后按成交数量激活止盈/止损单, 止盈止损单互为OCO
+    ///
+    /// 返回[入场单, 止盈单, 止损单]的订单id, 入场单被拒绝时仅返回入场单id
+    fn place_bracket(
+        &mut self,
+        entry: OrderRequest,
+        take_profit: Decimal,
+        stop_loss: Decimal,
+    ) -> Result<Vec<String>>;
+    /// 撤销交易对的全部未完成订单, 返回撤销的订单id
+    #[inline]
+    fn cancel_all(&mut self, code: &str) -> Result<Vec<String>> {
+        let ids = self
+            .get_context()
+            .orders
+            .values()
+            .filter(|order| {
+                order.code == code
+                    && matches!(
+                        order.status,
+                        OrderStatus::New
+                            | OrderStatus::Pending
+                            | OrderStatus::PartiallyFilled
+                            | OrderStatus::Waiting
+                    )
+            })
+            .map(|order| order.id.clone())
+            .collect::<Vec<_>>();
+        self.cancel_orders(&ids)?;
+        Ok(ids)
+    }
     /// 做多开仓
     #[inline]
     fn buy(&mut self, code: &str, size: Decimal, price: Option<Decimal>) -> Result<String> {
@@ -466,6 +1227,38 @@ pub trait Engine {
             price,
         )
     }
+    /// 单向持仓下单: 按当前持仓净额冲抵, 超出部分反向开仓
+    #[inline]
+    fn place_net_order(
+        &mut self,
+        code: &str,
+        side: Side,
+        size: Decimal,
+        price: Option<Decimal>,
+    ) -> Result<String> {
+        let context = self.get_context();
+        ensure!(
+            context.is_one_way(),
+            "net order requires one way position mode"
+        );
+        ensure!(
+            context.positions.contains_key(code),
+            "symbol not found: {code}"
+        );
+        let direction = context.net_direction(code, side);
+        self.place_order(
+            code,
+            if price.is_some() {
+                OrderType::Limit
+            } else {
+                OrderType::Market
+            },
+            direction,
+            side,
+            size,
+            price,
+        )
+    }
 }
 
 #[derive(Clone, Copy)]
@@ -498,12 +1291,72 @@ pub trait Strategy {
     fn on_order(&mut self, engine: &mut dyn Engine, order_id: &str) -> Result<()>;
     /// 持仓更新
     fn on_position(&mut self, engine: &mut dyn Engine, code: &str) -> Result<()>;
+    /// 强制平仓
+    fn on_liquidation(
+        &mut self,
+        engine: &mut dyn Engine,
+        code: &str,
+        direction: Direction,
+    ) -> Result<()>;
+    /// 自定义滑点, 返回 None 时使用回测滑点模型
+    fn on_slippage(
+        &mut self,
+        engine: &mut dyn Engine,
+        order: &Order,
+        price: Decimal,
+        size: Decimal,
+    ) -> Result<Option<Decimal>>;
+}
+
+/// 滑点模型
+pub trait SlippageModel {
+    /// 计算成交价格相对参考价格的滑点比例
+    fn calc(
+        &mut self,
+        order: &Order,
+        price: Decimal,
+        size: Decimal,
+        bars: &DataFrame,
+        bar_idx: usize,
+    ) -> Result<Decimal>;
+}
+
+/// 回测数据源: K线、交易对元数据、资金费率及秒级K线
+pub trait DataSource {
+    /// 加载K线(开始时间前 history_bar_len 根至结束时间), 按周期对齐且不能缺失
+    ///
+    /// 字段: time, open, high, low, close, size, cash, trades, taker_size, taker_cash
+    fn load(
+        &self,
+        code: &str,
+        start_time: Time,
+        end_time: Time,
+        interval: &str,
+        history_bar_len: usize,
+    ) -> Result<DataFrame>;
+
+    /// 交易对(合约属性及杠杆分层), 数据源未提供时返回 None
+    fn symbol(&self, code: &str) -> Result<Option<Symbol>>;
+
+    /// 截至结束时间的历史资金费率(结算时间, 费率), 未提供时返回 None, 按交易对资金费率及结算间隔结算
+    fn funding_rates(
+        &self,
+        _code: &str,
+        _end_time: Time,
+    ) -> Result<Option<VecDeque<(Time, Decimal)>>> {
+        Ok(None)
+    }
+
+    /// 时间范围 [开始时间, 结束时间) 内的秒级K线, 未提供时返回 None
+    fn sub_bars(&self, _code: &str, _start_time: Time, _end_time: Time) -> Result<Option<SubBars>> {
+        Ok(None)
+    }
 }
 
 /// 一年的天数（考虑闰年，更准确）
 const DAYS_PER_YEAR: f64 = 365.25;
-/// 一年的分钟数（用于年化波动率计算）
-const MINUTES_PER_YEAR: f64 = DAYS_PER_YEAR * 24.0 * 60.0;
+/// 一年的秒数（用于年化波动率计算）
+const SECONDS_PER_YEAR: f64 = DAYS_PER_YEAR * 24.0 * 60.0 * 60.0;
 
 /// 回测报告
 #[derive(Debug, Clone)]
@@ -534,6 +1387,26 @@ pub struct Report {
 
     /// 总手续费
     pub fee: f64,
+    /// 总资金费(正数为支出, 负数为收入)
+    pub funding: f64,
+    /// 总滑点成本
+    pub slippage: f64,
+
+    /// 按订单标签统计
+    pub tags: Map<String, TagReport>,
+}
+
+/// 订单标签统计
+#[derive(Debug, Clone, Default)]
+pub struct TagReport {
+    /// 已实现净盈亏(扣除开仓及平仓手续费)
+    pub pnl: f64,
+    /// 手续费
+    pub fee: f64,
+    /// 胜率
+    pub win_rate: f64,
+    /// 交易次数
+    pub trade_cnt: usize,
 }
 
 impl Report {
@@ -544,7 +1417,9 @@ impl Report {
         hist_eq: &[f64],
         start: Time,
         end: Time,
+        interval: Duration,
         trades: &[Trade],
+        fundings: &[Funding],
     ) -> Result<Self> {
         let fee = trades
             .iter()
@@ -553,6 +1428,20 @@ impl Report {
             .to_f64()
             .unwrap_or(0.0);
 
+        let funding = fundings
+            .iter()
+            .map(|funding| funding.cash)
+            .sum::<Decimal>()
+            .to_f64()
+            .unwrap_or(0.0);
+
+        let slippage = trades
+            .iter()
+            .map(|trade| trade.slippage)
+            .sum::<Decimal>()
+            .to_f64()
+            .unwrap_or(0.0);
+
         let days = (end - start).num_days() as f64;
         let days = days.max(1.0);
 
@@ -619,9 +1508,12 @@ impl Report {
                     .get(0)
                     .unwrap_or(0.0);
 
-                let vol = (ret_std * MINUTES_PER_YEAR.sqrt()).max(0.0);
+                // 每年的K线数量
+                let periods_per_year = SECONDS_PER_YEAR / interval.num_seconds().max(1) as f64;
+
+                let vol = (ret_std * periods_per_year.sqrt()).max(0.0);
 
-                let ann_down_vol = (down_std * MINUTES_PER_YEAR.sqrt()).max(0.0);
+                let ann_down_vol = (down_std * periods_per_year.sqrt()).max(0.0);
 
                 let sor = if ann_down_vol > 0.0 {
                     ar / ann_down_vol
@@ -707,6 +1599,163 @@ impl Report {
             pl_ratio,
             trade_cnt,
             fee,
+            funding,
+            slippage,
+            tags: Self::calc_tags(trades),
         })
     }
+
+    /// 按订单标签统计盈亏, 交易次数与胜率口径同整体报告(仅统计已实现盈亏非零的成交)
+    /// 开仓手续费计入开仓订单标签, 平仓盈亏(含止盈止损、强平及无标签平仓)按先开先平计入对应开仓标签
+    fn calc_tags(trades: &[Trade]) -> Map<String, TagReport> {
+        let mut tags = Map::<String, TagReport>::new();
+        let mut win_cnts = Map::<String, usize>::new();
+        for trade in trades {
+            let is_open = matches!(
+                (trade.direction, trade.side),
+                (Direction::Long, Side::Buy) | (Direction::Short, Side::Sell)
+            );
+            if is_open {
+                if let Some(tag) = trade.tag.as_ref() {
+                    let fee = trade.fee.to_f64().unwrap_or(0.0);
+                    let report = tags.entry(tag.clone()).or_default();
+                    report.pnl -= fee;
+                    report.fee += fee;
+                }
+                continue;
+            }
+
+            // 平仓盈亏已扣除平仓手续费, 按开仓数量比例分摊
+            for (tag, size) in &trade.entry_tags {
+                let Some(tag) = tag.as_ref() else {
+                    continue;
+                };
+                if trade.size.is_zero() {
+                    continue;
+                }
+                let ratio = size / trade.size;
+                let rpl = (trade.rpl * ratio).to_f64().unwrap_or(0.0);
+                let report = tags.entry(tag.clone()).or_default();
+                report.pnl += rpl;
+                report.fee += (trade.fee * ratio).to_f64().unwrap_or(0.0);
+                if rpl != 0.0 {
+                    report.trade_cnt += 1;
+                }
+                if rpl > 0.0 {
+                    *win_cnts.entry(tag.clone()).or_default() += 1;
+                }
+            }
+        }
+        for (tag, report) in tags.iter_mut() {
+            if report.trade_cnt > 0 {
+                let win_cnt = win_cnts.get(tag).copied().unwrap_or(0);
+                report.win_rate = win_cnt as f64 / report.trade_cnt as f64;
+            }
+        }
+        tags
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 币本位合约: 面值 100 美元
+    fn inverse_symbol() -> Symbol {
+        let mut symbol = Symbol::new("BTC");
+        symbol.face_val = dec!(100);
+        symbol.inverse = true;
+        symbol
+    }
+
+    /// 单交易对全仓账户, 持仓均价与标记价格均为 10000
+    fn new_context(symbol: Symbol, cash: Decimal, direction: Direction, size: Decimal) -> Context {
+        let mut context = Context::new(cash, &["BTC".to_string()]).unwrap();
+        let mut symbol = symbol;
+        symbol.mark_price = dec!(10000);
+        context.symbols.insert("BTC".into(), symbol);
+        let dir_pos = context.positions.get_mut("BTC").unwrap().get_mut(direction);
+        dir_pos.price = dec!(10000);
+        dir_pos.size = size;
+        context
+    }
+
+    #[test]
+    fn test_calc_cash_inverse() {
+        let symbol = inverse_symbol();
+        // 10 张 × 100 美元 / 12500 = 0.08 BTC
+        assert_eq!(symbol.calc_cash(dec!(12500), dec!(10)), dec!(0.08));
+        assert_eq!(symbol.calc_cash(dec!(0), dec!(10)), dec!(0));
+    }
+
+    #[test]
+    fn test_calc_pnl_inverse() {
+        let symbol = inverse_symbol();
+        // 1000 美元 × (1 / 10000 - 1 / 12500) = 0.02 BTC
+        let pnl = |direction| symbol.calc_pnl(direction, dec!(10000), dec!(12500), dec!(10));
+        assert_eq!(pnl(Direction::Long), dec!(0.02));
+        assert_eq!(pnl(Direction::Short), dec!(-0.02));
+        assert_eq!(
+            symbol.calc_pnl(Direction::Long, dec!(0), dec!(12500), dec!(10)),
+            dec!(0)
+        );
+    }
+
+    #[test]
+    fn test_calc_liq_price_linear() {
+        // 多头: (10000 × 1 - 1036) / (1 × (1 - 0.004)) = 9000
+        let context = new_context(Symbol::new("BTC"), dec!(1036), Direction::Long, dec!(1));
+        assert_eq!(context.calc_liq_price("BTC", Direction::Long), dec!(9000));
+
+        // 空头: (10000 × 1 + 1044) / (1 × (1 + 0.004)) = 11000
+        let context = new_context(Symbol::new("BTC"), dec!(1044), Direction::Short, dec!(1));
+        assert_eq!(context.calc_liq_price("BTC", Direction::Short), dec!(11000));
+
+        // 无持仓
+        assert_eq!(context.calc_liq_price("BTC", Direction::Long), dec!(0));
+    }
+
+    #[test]
+    fn test_calc_liq_price_inverse() {
+        // 10 张 × 100 美元 = 1000 美元, 折合 0.1 BTC
+        // 多头: 1000 × (1 + 0.004) / (0.1008 + 0.1) = 5000
+        let context = new_context(inverse_symbol(), dec!(0.1008), Direction::Long, dec!(10));
+        assert_eq!(context.calc_liq_price("BTC", Direction::Long), dec!(5000));
+
+        // 空头: 1000 × (1 - 0.004) / (0.1 - 0.017) = 12000
+        let context = new_context(inverse_symbol(), dec!(0.017), Direction::Short, dec!(10));
+        assert_eq!(context.calc_liq_price("BTC", Direction::Short), dec!(12000));
+
+        // 空头保证金足以覆盖任意上涨时不会强平
+        let context = new_context(inverse_symbol(), dec!(0.2), Direction::Short, dec!(10));
+        assert_eq!(
+            context.calc_liq_price("BTC", Direction::Short),
+            Decimal::MAX
+        );
+    }
+
+    #[test]
+    fn test_migrate_legacy_dir() {
+        let data_dir =
+            std::env::temp_dir().join(format!("fuxi-quant-legacy-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&data_dir);
+        std::fs::create_dir_all(data_dir.join("bars")).unwrap();
+        std::fs::write(data_dir.join("bars").join("BTCUSDT.data"), "bars").unwrap();
+        std::fs::write(data_dir.join("symbols.json"), "legacy").unwrap();
+        std::fs::create_dir_all(data_dir.join("um")).unwrap();
+        std::fs::write(data_dir.join("um").join("symbols.json"), "um").unwrap();
+
+        let data_dir_str = data_dir.to_string_lossy().to_string();
+        Market::Um.migrate_legacy_dir(&data_dir_str).unwrap();
+
+        // 旧目录的K线移动到市场目录, 市场目录中已有的元数据文件保留
+        let um_dir = Market::Um.dir(&data_dir_str);
+        assert!(um_dir.join("bars").join("BTCUSDT.data").exists());
+        assert!(!data_dir.join("bars").exists());
+        let read = |path: PathBuf| std::fs::read_to_string(path).unwrap();
+        assert_eq!(read(um_dir.join("symbols.json")), "um");
+        assert_eq!(read(data_dir.join("symbols.json")), "legacy");
+
+        std::fs::remove_dir_all(&data_dir).unwrap();
+    }
 }
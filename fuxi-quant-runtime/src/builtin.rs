@@ -1,7 +1,7 @@
 use crate::common::*;
 use fuxi_quant_core::types::*;
 use polars::frame::DataFrame;
-use rhai::{Array, Dynamic, Engine, exported_module, plugin::*};
+use rhai::{Array, Dynamic, Engine, Map, exported_module, plugin::*};
 use rust_decimal::Decimal;
 
 pub fn register(engine: &mut Engine) {
@@ -36,11 +36,135 @@ pub mod module {
         this.to_string()
     }
 
+    #[rhai_fn(name = "to_string", pure, global)]
+    pub fn reject_reason_to_string(this: &mut RejectReason) -> String {
+        this.to_string()
+    }
+
+    #[rhai_fn(name = "to_string", pure, global)]
+    pub fn time_in_force_to_string(this: &mut TimeInForce) -> String {
+        this.to_string()
+    }
+
     #[rhai_fn(name = "to_string", pure, global)]
     pub fn timer_to_string(this: &mut Timer) -> String {
         this.to_string()
     }
 
+    #[rhai_fn(name = "to_string", pure, global)]
+    pub fn margin_mode_to_string(this: &mut MarginMode) -> String {
+        this.to_string()
+    }
+
+    #[rhai_fn(name = "to_string", pure, global)]
+    pub fn position_mode_to_string(this: &mut PositionMode) -> String {
+        this.to_string()
+    }
+
+    // ================================================================ //
+    // 比较
+    // ================================================================ //
+
+    #[rhai_fn(name = "==", global)]
+    pub fn mode_eq(a: Mode, b: Mode) -> bool {
+        a == b
+    }
+
+    #[rhai_fn(name = "!=", global)]
+    pub fn mode_neq(a: Mode, b: Mode) -> bool {
+        a != b
+    }
+
+    #[rhai_fn(name = "==", global)]
+    pub fn order_type_eq(a: OrderType, b: OrderType) -> bool {
+        a == b
+    }
+
+    #[rhai_fn(name = "!=", global)]
+    pub fn order_type_neq(a: OrderType, b: OrderType) -> bool {
+        a != b
+    }
+
+    #[rhai_fn(name = "==", global)]
+    pub fn direction_eq(a: Direction, b: Direction) -> bool {
+        a == b
+    }
+
+    #[rhai_fn(name = "!=", global)]
+    pub fn direction_neq(a: Direction, b: Direction) -> bool {
+        a != b
+    }
+
+    #[rhai_fn(name = "==", global)]
+    pub fn side_eq(a: Side, b: Side) -> bool {
+        a == b
+    }
+
+    #[rhai_fn(name = "!=", global)]
+    pub fn side_neq(a: Side, b: Side) -> bool {
+        a != b
+    }
+
+    #[rhai_fn(name = "==", global)]
+    pub fn order_status_eq(a: OrderStatus, b: OrderStatus) -> bool {
+        a == b
+    }
+
+    #[rhai_fn(name = "!=", global)]
+    pub fn order_status_neq(a: OrderStatus, b: OrderStatus) -> bool {
+        a != b
+    }
+
+    #[rhai_fn(name = "==", global)]
+    pub fn reject_reason_eq(a: RejectReason, b: RejectReason) -> bool {
+        a == b
+    }
+
+    #[rhai_fn(name = "!=", global)]
+    pub fn reject_reason_neq(a: RejectReason, b: RejectReason) -> bool {
+        a != b
+    }
+
+    #[rhai_fn(name = "==", global)]
+    pub fn time_in_force_eq(a: TimeInForce, b: TimeInForce) -> bool {
+        a == b
+    }
+
+    #[rhai_fn(name = "!=", global)]
+    pub fn time_in_force_neq(a: TimeInForce, b: TimeInForce) -> bool {
+        a != b
+    }
+
+    #[rhai_fn(name = "==", global)]
+    pub fn timer_eq(a: Timer, b: Timer) -> bool {
+        a == b
+    }
+
+    #[rhai_fn(name = "!=", global)]
+    pub fn timer_neq(a: Timer, b: Timer) -> bool {
+        a != b
+    }
+
+    #[rhai_fn(name = "==", global)]
+    pub fn margin_mode_eq(a: MarginMode, b: MarginMode) -> bool {
+        a == b
+    }
+
+    #[rhai_fn(name = "!=", global)]
+    pub fn margin_mode_neq(a: MarginMode, b: MarginMode) -> bool {
+        a != b
+    }
+
+    #[rhai_fn(name = "==", global)]
+    pub fn position_mode_eq(a: PositionMode, b: PositionMode) -> bool {
+        a == b
+    }
+
+    #[rhai_fn(name = "!=", global)]
+    pub fn position_mode_neq(a: PositionMode, b: PositionMode) -> bool {
+        a != b
+    }
+
     // ================================================================ //
     // 常量
     // ================================================================ //
@@ -50,6 +174,11 @@ pub mod module {
 
     pub const LIMIT: OrderType = OrderType::Limit;
     pub const MARKET: OrderType = OrderType::Market;
+    pub const STOP_MARKET: OrderType = OrderType::StopMarket;
+    pub const STOP_LIMIT: OrderType = OrderType::StopLimit;
+    pub const TAKE_PROFIT_MARKET: OrderType = OrderType::TakeProfitMarket;
+    pub const TAKE_PROFIT_LIMIT: OrderType = OrderType::TakeProfitLimit;
+    pub const TRAILING_STOP: OrderType = OrderType::TrailingStop;
 
     pub const LONG: Direction = Direction::Long;
     pub const SHORT: Direction = Direction::Short;
@@ -59,10 +188,32 @@ pub mod module {
 
     pub const ORD_NEW: OrderStatus = OrderStatus::New;
     pub const ORD_PENDING: OrderStatus = OrderStatus::Pending;
+    pub const ORD_PARTIALLY_FILLED: OrderStatus = OrderStatus::PartiallyFilled;
     pub const ORD_FILLED: OrderStatus = OrderStatus::Filled;
     pub const ORD_CANCELING: OrderStatus = OrderStatus::Canceling;
     pub const ORD_CANCELED: OrderStatus = OrderStatus::Canceled;
     pub const ORD_REJECTED: OrderStatus = OrderStatus::Rejected;
+    pub const ORD_WAITING: OrderStatus = OrderStatus::Waiting;
+
+    pub const REJ_UNKNOWN_SYMBOL: RejectReason = RejectReason::UnknownSymbol;
+    pub const REJ_INSUFFICIENT_MARGIN: RejectReason = RejectReason::InsufficientMargin;
+    pub const REJ_INSUFFICIENT_POSITION: RejectReason = RejectReason::InsufficientPosition;
+    pub const REJ_BELOW_MIN_SIZE: RejectReason = RejectReason::BelowMinSize;
+    pub const REJ_BELOW_MIN_NOTIONAL: RejectReason = RejectReason::BelowMinNotional;
+    pub const REJ_INVALID_TICK: RejectReason = RejectReason::InvalidTick;
+    pub const REJ_REDUCE_ONLY: RejectReason = RejectReason::ReduceOnly;
+    pub const REJ_POST_ONLY: RejectReason = RejectReason::PostOnly;
+    pub const REJ_EXCEED_MAX_LEVER: RejectReason = RejectReason::ExceedMaxLever;
+
+    pub const GTC: TimeInForce = TimeInForce::Gtc;
+    pub const IOC: TimeInForce = TimeInForce::Ioc;
+    pub const FOK: TimeInForce = TimeInForce::Fok;
+
+    pub const CROSS: MarginMode = MarginMode::Cross;
+    pub const ISOLATED: MarginMode = MarginMode::Isolated;
+
+    pub const HEDGE: PositionMode = PositionMode::Hedge;
+    pub const ONE_WAY: PositionMode = PositionMode::OneWay;
 
     pub const DAILY: Timer = Timer::Daily;
     pub const HOURLY: Timer = Timer::Hourly;
@@ -84,6 +235,19 @@ pub mod module {
         engine.get().get_bars(code, all).map_err(to_rt_err)
     }
 
+    #[rhai_fn(name = "bars", pure, global, return_raw)]
+    pub fn api_interval_bars(
+        engine: &mut EngineProvider,
+        code: &str,
+        interval: &str,
+        n: i64,
+    ) -> RTResult<DataFrame> {
+        engine
+            .get()
+            .get_interval_bars(code, interval, n.max(0) as usize)
+            .map_err(to_rt_err)
+    }
+
     #[rhai_fn(name = "signals", pure, global)]
     pub fn api_signals(engine: &mut EngineProvider) -> DataFrame {
         engine.get().get_signals()
@@ -125,11 +289,248 @@ pub mod module {
             .map_err(to_rt_err)
     }
 
+    /// 解析下单选项
+    ///
+    /// 支持: trigger_price, callback_rate, tif, post_only, reduce_only, client_id, tag
+    fn order_request(
+        code: &str,
+        type_: OrderType,
+        direction: Direction,
+        side: Side,
+        size: Decimal,
+        price: Option<Decimal>,
+        opts: Map,
+    ) -> RTResult<OrderRequest> {
+        let mut req = OrderRequest::new(code, type_, direction, side, size, price);
+        for (key, value) in opts {
+            match key.as_str() {
+                "trigger_price" => req.trigger_price = Some(value.as_decimal().map_err(to_rt_err)?),
+                "callback_rate" => req.callback_rate = Some(value.as_decimal().map_err(to_rt_err)?),
+                "tif" => {
+                    req.time_in_force = value
+                        .clone()
+                        .try_cast_result::<TimeInForce>()
+                        .map_err(|_| to_rt_err("tif must be GTC, IOC or FOK"))?
+                }
+                "post_only" => req.post_only = value.as_bool().map_err(to_rt_err)?,
+                "reduce_only" => req.reduce_only = value.as_bool().map_err(to_rt_err)?,
+                "client_id" => req.client_id = Some(value.into_string().map_err(to_rt_err)?),
+                "tag" => req.tag = Some(value.into_string().map_err(to_rt_err)?),
+                _ => return Err(to_rt_err(format!("unknown order option: {key}"))),
+            }
+        }
+        Ok(req)
+    }
+
+    #[rhai_fn(name = "place_order", pure, global, return_raw)]
+    pub fn api_place_order_3(
+        engine: &mut EngineProvider,
+        code: &str,
+        type_: OrderType,
+        direction: Direction,
+        side: Side,
+        size: Decimal,
+        opts: Map,
+    ) -> RTResult<String> {
+        let req = order_request(code, type_, direction, side, size, None, opts)?;
+        engine.get().submit_order(req).map_err(to_rt_err)
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    #[rhai_fn(name = "place_order", pure, global, return_raw)]
+    pub fn api_place_order_4(
+        engine: &mut EngineProvider,
+        code: &str,
+        type_: OrderType,
+        direction: Direction,
+        side: Side,
+        size: Decimal,
+        price: Decimal,
+        opts: Map,
+    ) -> RTResult<String> {
+        let req = order_request(code, type_, direction, side, size, Some(price), opts)?;
+        engine.get().submit_order(req).map_err(to_rt_err)
+    }
+
+    #[rhai_fn(name = "place_order", pure, global, return_raw)]
+    pub fn api_place_net_order(
+        engine: &mut EngineProvider,
+        code: &str,
+        side: Side,
+        size: Decimal,
+    ) -> RTResult<String> {
+        engine
+            .get()
+            .place_net_order(code, side, size, None)
+            .map_err(to_rt_err)
+    }
+
+    #[rhai_fn(name = "place_order", pure, global, return_raw)]
+    pub fn api_place_net_order_2(
+        engine: &mut EngineProvider,
+        code: &str,
+        side: Side,
+        size: Decimal,
+        price: Decimal,
+    ) -> RTResult<String> {
+        engine
+            .get()
+            .place_net_order(code, side, size, Some(price))
+            .map_err(to_rt_err)
+    }
+
+    #[rhai_fn(name = "position_mode", pure, global)]
+    pub fn api_position_mode(engine: &mut EngineProvider) -> PositionMode {
+        engine.get().get_context().position_mode
+    }
+
     #[rhai_fn(name = "cancel_order", pure, global, return_raw)]
     pub fn api_cancel_order(engine: &mut EngineProvider, id: &str) -> RTResult<()> {
         engine.get().cancel_order(id).map_err(to_rt_err)
     }
 
+    #[rhai_fn(name = "amend_order", pure, global, return_raw)]
+    pub fn api_amend_order(
+        engine: &mut EngineProvider,
+        id: &str,
+        price: Decimal,
+        size: Decimal,
+    ) -> RTResult<()> {
+        engine.get().amend_order(id, price, size).map_err(to_rt_err)
+    }
+
+    /// 解析批量下单项
+    ///
+    /// 必填: code, side, size; 可选: direction(默认开仓方向), type(有 price 时默认 LIMIT, 否则 MARKET), price 及下单选项
+    fn batch_request(item: Dynamic) -> RTResult<OrderRequest> {
+        let mut opts = item
+            .try_cast_result::<Map>()
+            .map_err(|_| to_rt_err("batch order must be a map"))?;
+        let mut take = |key: &str| {
+            opts.remove(key)
+                .ok_or_else(|| to_rt_err(format!("batch order missing {key}")))
+        };
+        let code = take("code")?.into_string().map_err(to_rt_err)?;
+        let side = take("side")?
+            .try_cast_result::<Side>()
+            .map_err(|_| to_rt_err("side must be BUY or SELL"))?;
+        let size = take("size")?.as_decimal().map_err(to_rt_err)?;
+        let price = match opts.remove("price") {
+            Some(value) => Some(value.as_decimal().map_err(to_rt_err)?),
+            None => None,
+        };
+        let direction = match opts.remove("direction") {
+            Some(value) => value
+                .try_cast_result::<Direction>()
+                .map_err(|_| to_rt_err("direction must be LONG or SHORT"))?,
+            None => side.open_direction(),
+        };
+        let type_ = match opts.remove("type") {
+            Some(value) => value
+                .try_cast_result::<OrderType>()
+                .map_err(|_| to_rt_err("invalid order type"))?,
+            None if price.is_some() => OrderType::Limit,
+            None => OrderType::Market,
+        };
+        order_request(&code, type_, direction, side, size, price, opts)
+    }
+
+    #[rhai_fn(name = "place_orders", pure, global, return_raw)]
+    pub fn api_place_orders(engine: &mut EngineProvider, batch: Array) -> RTResult<Array> {
+        let reqs = batch
+            .into_iter()
+            .map(batch_request)
+            .collect::<RTResult<Vec<_>>>()?;
+        engine
+            .get()
+            .place_orders(reqs)
+            .map(|ids| ids.into_iter().map(Dynamic::from).collect())
+            .map_err(to_rt_err)
+    }
+
+    #[rhai_fn(name = "oco", pure, global, return_raw)]
+    pub fn api_oco(engine: &mut EngineProvider, first: Map, second: Map) -> RTResult<Array> {
+        let first = batch_request(Dynamic::from(first))?;
+        let second = batch_request(Dynamic::from(second))?;
+        engine
+            .get()
+            .place_oco(first, second)
+            .map(|ids| ids.into_iter().map(Dynamic::from).collect())
+            .map_err(to_rt_err)
+    }
+
+    /// 括号单: 止盈高于止损时做多, 否则做空
+    fn bracket_request(
+        code: &str,
+        size: Decimal,
+        price: Option<Decimal>,
+        take_profit: Decimal,
+        stop_loss: Decimal,
+    ) -> OrderRequest {
+        let side = if take_profit > stop_loss {
+            Side::Buy
+        } else {
+            Side::Sell
+        };
+        let type_ = if price.is_some() {
+            OrderType::Limit
+        } else {
+            OrderType::Market
+        };
+        OrderRequest::new(code, type_, side.open_direction(), side, size, price)
+    }
+
+    #[rhai_fn(name = "bracket", pure, global, return_raw)]
+    pub fn api_bracket(
+        engine: &mut EngineProvider,
+        code: &str,
+        size: Decimal,
+        take_profit: Decimal,
+        stop_loss: Decimal,
+    ) -> RTResult<Array> {
+        let req = bracket_request(code, size, None, take_profit, stop_loss);
+        engine
+            .get()
+            .place_bracket(req, take_profit, stop_loss)
+            .map(|ids| ids.into_iter().map(Dynamic::from).collect())
+            .map_err(to_rt_err)
+    }
+
+    #[rhai_fn(name = "bracket", pure, global, return_raw)]
+    pub fn api_bracket_2(
+        engine: &mut EngineProvider,
+        code: &str,
+        size: Decimal,
+        entry: Decimal,
+        take_profit: Decimal,
+        stop_loss: Decimal,
+    ) -> RTResult<Array> {
+        let req = bracket_request(code, size, Some(entry), take_profit, stop_loss);
+        engine
+            .get()
+            .place_bracket(req, take_profit, stop_loss)
+            .map(|ids| ids.into_iter().map(Dynamic::from).collect())
+            .map_err(to_rt_err)
+    }
+
+    #[rhai_fn(name = "cancel_orders", pure, global, return_raw)]
+    pub fn api_cancel_orders(engine: &mut EngineProvider, ids: Array) -> RTResult<()> {
+        let ids = ids
+            .into_iter()
+            .map(|id| id.into_string().map_err(to_rt_err))
+            .collect::<RTResult<Vec<_>>>()?;
+        engine.get().cancel_orders(&ids).map_err(to_rt_err)
+    }
+
+    #[rhai_fn(name = "cancel_all", pure, global, return_raw)]
+    pub fn api_cancel_all(engine: &mut EngineProvider, code: &str) -> RTResult<Array> {
+        engine
+            .get()
+            .cancel_all(code)
+            .map(|ids| ids.into_iter().map(Dynamic::from).collect())
+            .map_err(to_rt_err)
+    }
+
     #[rhai_fn(name = "buy", pure, global, return_raw)]
     pub fn api_buy(engine: &mut EngineProvider, code: &str, size: Decimal) -> RTResult<String> {
         engine.get().buy(code, size, None).map_err(to_rt_err)
@@ -199,6 +600,406 @@ pub mod module {
             .map_err(to_rt_err)
     }
 
+    // ================================================================ //
+    // 条件单
+    // ================================================================ //
+
+    #[allow(clippy::too_many_arguments)]
+    fn place_trigger_order(
+        engine: &mut EngineProvider,
+        code: &str,
+        limit_type: OrderType,
+        market_type: OrderType,
+        direction: Direction,
+        side: Side,
+        size: Decimal,
+        trigger: Decimal,
+        price: Option<Decimal>,
+    ) -> RTResult<String> {
+        let type_ = if price.is_some() {
+            limit_type
+        } else {
+            market_type
+        };
+        engine
+            .get()
+            .place_trigger_order(
+                code,
+                type_,
+                direction,
+                side,
+                size,
+                price,
+                Some(trigger),
+                None,
+            )
+            .map_err(to_rt_err)
+    }
+
+    fn place_stop_order(
+        engine: &mut EngineProvider,
+        code: &str,
+        direction: Direction,
+        side: Side,
+        size: Decimal,
+        trigger: Decimal,
+        price: Option<Decimal>,
+    ) -> RTResult<String> {
+        place_trigger_order(
+            engine,
+            code,
+            OrderType::StopLimit,
+            OrderType::StopMarket,
+            direction,
+            side,
+            size,
+            trigger,
+            price,
+        )
+    }
+
+    fn place_take_profit_order(
+        engine: &mut EngineProvider,
+        code: &str,
+        direction: Direction,
+        side: Side,
+        size: Decimal,
+        trigger: Decimal,
+        price: Option<Decimal>,
+    ) -> RTResult<String> {
+        place_trigger_order(
+            engine,
+            code,
+            OrderType::TakeProfitLimit,
+            OrderType::TakeProfitMarket,
+            direction,
+            side,
+            size,
+            trigger,
+            price,
+        )
+    }
+
+    fn place_trailing_order(
+        engine: &mut EngineProvider,
+        code: &str,
+        direction: Direction,
+        side: Side,
+        size: Decimal,
+        callback_rate: Decimal,
+        activation: Option<Decimal>,
+    ) -> RTResult<String> {
+        engine
+            .get()
+            .place_trigger_order(
+                code,
+                OrderType::TrailingStop,
+                direction,
+                side,
+                size,
+                None,
+                activation,
+                Some(callback_rate),
+            )
+            .map_err(to_rt_err)
+    }
+
+    #[rhai_fn(name = "stop_buy", pure, global, return_raw)]
+    pub fn api_stop_buy(
+        engine: &mut EngineProvider,
+        code: &str,
+        size: Decimal,
+        trigger: Decimal,
+    ) -> RTResult<String> {
+        place_stop_order(
+            engine,
+            code,
+            Direction::Long,
+            Side::Buy,
+            size,
+            trigger,
+            None,
+        )
+    }
+
+    #[rhai_fn(name = "stop_buy", pure, global, return_raw)]
+    pub fn api_stop_buy_2(
+        engine: &mut EngineProvider,
+        code: &str,
+        size: Decimal,
+        trigger: Decimal,
+        price: Decimal,
+    ) -> RTResult<String> {
+        place_stop_order(
+            engine,
+            code,
+            Direction::Long,
+            Side::Buy,
+            size,
+            trigger,
+            Some(price),
+        )
+    }
+
+    #[rhai_fn(name = "stop_sell", pure, global, return_raw)]
+    pub fn api_stop_sell(
+        engine: &mut EngineProvider,
+        code: &str,
+        size: Decimal,
+        trigger: Decimal,
+    ) -> RTResult<String> {
+        place_stop_order(
+            engine,
+            code,
+            Direction::Long,
+            Side::Sell,
+            size,
+            trigger,
+            None,
+        )
+    }
+
+    #[rhai_fn(name = "stop_sell", pure, global, return_raw)]
+    pub fn api_stop_sell_2(
+        engine: &mut EngineProvider,
+        code: &str,
+        size: Decimal,
+        trigger: Decimal,
+        price: Decimal,
+    ) -> RTResult<String> {
+        place_stop_order(
+            engine,
+            code,
+            Direction::Long,
+            Side::Sell,
+            size,
+            trigger,
+            Some(price),
+        )
+    }
+
+    #[rhai_fn(name = "stop_short", pure, global, return_raw)]
+    pub fn api_stop_short(
+        engine: &mut EngineProvider,
+        code: &str,
+        size: Decimal,
+        trigger: Decimal,
+    ) -> RTResult<String> {
+        place_stop_order(
+            engine,
+            code,
+            Direction::Short,
+            Side::Sell,
+            size,
+            trigger,
+            None,
+        )
+    }
+
+    #[rhai_fn(name = "stop_short", pure, global, return_raw)]
+    pub fn api_stop_short_2(
+        engine: &mut EngineProvider,
+        code: &str,
+        size: Decimal,
+        trigger: Decimal,
+        price: Decimal,
+    ) -> RTResult<String> {
+        place_stop_order(
+            engine,
+            code,
+            Direction::Short,
+            Side::Sell,
+            size,
+            trigger,
+            Some(price),
+        )
+    }
+
+    #[rhai_fn(name = "stop_cover", pure, global, return_raw)]
+    pub fn api_stop_cover(
+        engine: &mut EngineProvider,
+        code: &str,
+        size: Decimal,
+        trigger: Decimal,
+    ) -> RTResult<String> {
+        place_stop_order(
+            engine,
+            code,
+            Direction::Short,
+            Side::Buy,
+            size,
+            trigger,
+            None,
+        )
+    }
+
+    #[rhai_fn(name = "stop_cover", pure, global, return_raw)]
+    pub fn api_stop_cover_2(
+        engine: &mut EngineProvider,
+        code: &str,
+        size: Decimal,
+        trigger: Decimal,
+        price: Decimal,
+    ) -> RTResult<String> {
+        place_stop_order(
+            engine,
+            code,
+            Direction::Short,
+            Side::Buy,
+            size,
+            trigger,
+            Some(price),
+        )
+    }
+
+    #[rhai_fn(name = "tp_sell", pure, global, return_raw)]
+    pub fn api_tp_sell(
+        engine: &mut EngineProvider,
+        code: &str,
+        size: Decimal,
+        trigger: Decimal,
+    ) -> RTResult<String> {
+        place_take_profit_order(
+            engine,
+            code,
+            Direction::Long,
+            Side::Sell,
+            size,
+            trigger,
+            None,
+        )
+    }
+
+    #[rhai_fn(name = "tp_sell", pure, global, return_raw)]
+    pub fn api_tp_sell_2(
+        engine: &mut EngineProvider,
+        code: &str,
+        size: Decimal,
+        trigger: Decimal,
+        price: Decimal,
+    ) -> RTResult<String> {
+        place_take_profit_order(
+            engine,
+            code,
+            Direction::Long,
+            Side::Sell,
+            size,
+            trigger,
+            Some(price),
+        )
+    }
+
+    #[rhai_fn(name = "tp_cover", pure, global, return_raw)]
+    pub fn api_tp_cover(
+        engine: &mut EngineProvider,
+        code: &str,
+        size: Decimal,
+        trigger: Decimal,
+    ) -> RTResult<String> {
+        place_take_profit_order(
+            engine,
+            code,
+            Direction::Short,
+            Side::Buy,
+            size,
+            trigger,
+            None,
+        )
+    }
+
+    #[rhai_fn(name = "tp_cover", pure, global, return_raw)]
+    pub fn api_tp_cover_2(
+        engine: &mut EngineProvider,
+        code: &str,
+        size: Decimal,
+        trigger: Decimal,
+        price: Decimal,
+    ) -> RTResult<String> {
+        place_take_profit_order(
+            engine,
+            code,
+            Direction::Short,
+            Side::Buy,
+            size,
+            trigger,
+            Some(price),
+        )
+    }
+
+    #[rhai_fn(name = "trailing_sell", pure, global, return_raw)]
+    pub fn api_trailing_sell(
+        engine: &mut EngineProvider,
+        code: &str,
+        size: Decimal,
+        callback_rate: Decimal,
+    ) -> RTResult<String> {
+        place_trailing_order(
+            engine,
+            code,
+            Direction::Long,
+            Side::Sell,
+            size,
+            callback_rate,
+            None,
+        )
+    }
+
+    #[rhai_fn(name = "trailing_sell", pure, global, return_raw)]
+    pub fn api_trailing_sell_2(
+        engine: &mut EngineProvider,
+        code: &str,
+        size: Decimal,
+        callback_rate: Decimal,
+        activation: Decimal,
+    ) -> RTResult<String> {
+        place_trailing_order(
+            engine,
+            code,
+            Direction::Long,
+            Side::Sell,
+            size,
+            callback_rate,
+            Some(activation),
+        )
+    }
+
+    #[rhai_fn(name = "trailing_cover", pure, global, return_raw)]
+    pub fn api_trailing_cover(
+        engine: &mut EngineProvider,
+        code: &str,
+        size: Decimal,
+        callback_rate: Decimal,
+    ) -> RTResult<String> {
+        place_trailing_order(
+            engine,
+            code,
+            Direction::Short,
+            Side::Buy,
+            size,
+            callback_rate,
+            None,
+        )
+    }
+
+    #[rhai_fn(name = "trailing_cover", pure, global, return_raw)]
+    pub fn api_trailing_cover_2(
+        engine: &mut EngineProvider,
+        code: &str,
+        size: Decimal,
+        callback_rate: Decimal,
+        activation: Decimal,
+    ) -> RTResult<String> {
+        place_trailing_order(
+            engine,
+            code,
+            Direction::Short,
+            Side::Buy,
+            size,
+            callback_rate,
+            Some(activation),
+        )
+    }
+
     #[rhai_fn(name = "cash", pure, global)]
     pub fn api_cash(engine: &mut EngineProvider) -> Decimal {
         engine.get().get_context().cash
@@ -234,6 +1035,59 @@ pub mod module {
         engine.get().get_context().calc_equity()
     }
 
+    #[rhai_fn(name = "maint_margin", pure, global)]
+    pub fn api_calc_maint_margin(engine: &mut EngineProvider) -> Decimal {
+        engine.get().get_context().calc_maint_margin()
+    }
+
+    #[rhai_fn(name = "liq_price", pure, global, return_raw)]
+    pub fn api_calc_liq_price(
+        engine: &mut EngineProvider,
+        code: &str,
+        direction: Direction,
+    ) -> RTResult<Decimal> {
+        let context = engine.get().get_context();
+        if !context.positions.contains_key(code) {
+            return Err(to_rt_err(format!("position not found: {code}")));
+        }
+        Ok(context.calc_liq_price(code, direction))
+    }
+
+    #[rhai_fn(name = "set_margin_mode", pure, global, return_raw)]
+    pub fn api_set_margin_mode(
+        engine: &mut EngineProvider,
+        code: &str,
+        mode: MarginMode,
+    ) -> RTResult<()> {
+        engine.get().set_margin_mode(code, mode).map_err(to_rt_err)
+    }
+
+    #[rhai_fn(name = "add_margin", pure, global, return_raw)]
+    pub fn api_add_margin(
+        engine: &mut EngineProvider,
+        code: &str,
+        direction: Direction,
+        cash: Decimal,
+    ) -> RTResult<()> {
+        engine
+            .get()
+            .add_margin(code, direction, cash)
+            .map_err(to_rt_err)
+    }
+
+    #[rhai_fn(name = "remove_margin", pure, global, return_raw)]
+    pub fn api_remove_margin(
+        engine: &mut EngineProvider,
+        code: &str,
+        direction: Direction,
+        cash: Decimal,
+    ) -> RTResult<()> {
+        engine
+            .get()
+            .remove_margin(code, direction, cash)
+            .map_err(to_rt_err)
+    }
+
     #[rhai_fn(name = "pos_frozen_size", pure, global)]
     pub fn api_calc_pos_frozen_size(
         engine: &mut EngineProvider,
@@ -268,7 +1122,10 @@ pub mod module {
             .filter(|(_, order)| {
                 matches!(
                     order.status,
-                    OrderStatus::New | OrderStatus::Pending | OrderStatus::Canceling
+                    OrderStatus::New
+                        | OrderStatus::Pending
+                        | OrderStatus::PartiallyFilled
+                        | OrderStatus::Canceling
                 ) && order.size > order.filled
             })
             .map(|(_, order)| Dynamic::from(order.clone()))
@@ -286,7 +1143,10 @@ pub mod module {
                 order.code == code
                     && matches!(
                         order.status,
-                        OrderStatus::New | OrderStatus::Pending | OrderStatus::Canceling
+                        OrderStatus::New
+                            | OrderStatus::Pending
+                            | OrderStatus::PartiallyFilled
+                            | OrderStatus::Canceling
                     )
                     && order.size > order.filled
             })
@@ -299,8 +1159,64 @@ pub mod module {
         engine
             .get()
             .get_context()
-            .orders
-            .get(id)
+            .find_order(id)
+            .cloned()
+            .map_or(Dynamic::UNIT, Dynamic::from)
+    }
+
+    #[rhai_fn(name = "orders", pure, global)]
+    pub fn api_get_orders(engine: &mut EngineProvider) -> Array {
+        let context = engine.get().get_context();
+        context
+            .history_orders
+            .iter()
+            .chain(context.orders.values())
+            .map(|order| Dynamic::from(order.clone()))
+            .collect()
+    }
+
+    #[rhai_fn(name = "orders", pure, global)]
+    pub fn api_get_orders_2(engine: &mut EngineProvider, status: OrderStatus) -> Array {
+        let context = engine.get().get_context();
+        context
+            .history_orders
+            .iter()
+            .chain(context.orders.values())
+            .filter(|order| order.status == status)
+            .map(|order| Dynamic::from(order.clone()))
+            .collect()
+    }
+
+    #[rhai_fn(name = "trades", pure, global)]
+    pub fn api_get_trades(engine: &mut EngineProvider) -> Array {
+        engine
+            .get()
+            .get_context()
+            .trades
+            .iter()
+            .map(|trade| Dynamic::from(trade.clone()))
+            .collect()
+    }
+
+    #[rhai_fn(name = "trades", pure, global)]
+    pub fn api_get_trades_2(engine: &mut EngineProvider, code: &str) -> Array {
+        engine
+            .get()
+            .get_context()
+            .trades
+            .iter()
+            .filter(|trade| trade.code == code)
+            .map(|trade| Dynamic::from(trade.clone()))
+            .collect()
+    }
+
+    #[rhai_fn(name = "last_trade", pure, global)]
+    pub fn api_get_last_trade(engine: &mut EngineProvider) -> Dynamic {
+        engine
+            .get()
+            .get_context()
+            .trades
+            .back()
             .cloned()
             .map_or(Dynamic::UNIT, Dynamic::from)
     }
@@ -425,6 +1341,132 @@ pub mod module {
         order.time.timestamp_millis()
     }
 
+    #[rhai_fn(get = "active_time", pure, global)]
+    pub fn get_order_active_time(order: &mut Order) -> i64 {
+        order.active_time.timestamp_millis()
+    }
+
+    #[rhai_fn(get = "cancel_time", pure, global)]
+    pub fn get_order_cancel_time(order: &mut Order) -> Dynamic {
+        order
+            .cancel_time
+            .map_or(Dynamic::UNIT, |time| Dynamic::from(time.timestamp_millis()))
+    }
+
+    #[rhai_fn(get = "trigger_price", pure, global)]
+    pub fn get_order_trigger_price(order: &mut Order) -> Decimal {
+        order.trigger_price.unwrap_or_default()
+    }
+
+    #[rhai_fn(get = "callback_rate", pure, global)]
+    pub fn get_order_callback_rate(order: &mut Order) -> Decimal {
+        order.callback_rate.unwrap_or_default()
+    }
+
+    #[rhai_fn(get = "trail_price", pure, global)]
+    pub fn get_order_trail_price(order: &mut Order) -> Decimal {
+        order.trail_price.unwrap_or_default()
+    }
+
+    #[rhai_fn(get = "triggered", pure, global)]
+    pub fn get_order_triggered(order: &mut Order) -> bool {
+        order.triggered
+    }
+
+    #[rhai_fn(get = "tif", pure, global)]
+    pub fn get_order_time_in_force(order: &mut Order) -> TimeInForce {
+        order.time_in_force
+    }
+
+    #[rhai_fn(get = "post_only", pure, global)]
+    pub fn get_order_post_only(order: &mut Order) -> bool {
+        order.post_only
+    }
+
+    #[rhai_fn(get = "reduce_only", pure, global)]
+    pub fn get_order_reduce_only(order: &mut Order) -> bool {
+        order.reduce_only
+    }
+
+    #[rhai_fn(get = "reason", pure, global)]
+    pub fn get_order_reason(order: &mut Order) -> Dynamic {
+        order.reason.map_or(Dynamic::UNIT, Dynamic::from)
+    }
+
+    #[rhai_fn(get = "parent_id", pure, global)]
+    pub fn get_order_parent_id(order: &mut Order) -> Dynamic {
+        order.parent_id.clone().map_or(Dynamic::UNIT, Dynamic::from)
+    }
+
+    #[rhai_fn(get = "oco_id", pure, global)]
+    pub fn get_order_oco_id(order: &mut Order) -> Dynamic {
+        order.oco_id.clone().map_or(Dynamic::UNIT, Dynamic::from)
+    }
+
+    #[rhai_fn(get = "tag", pure, global)]
+    pub fn get_order_tag(order: &mut Order) -> Dynamic {
+        order.tag.clone().map_or(Dynamic::UNIT, Dynamic::from)
+    }
+
+    // ================================================================ //
+    // 成交记录
+    // ================================================================ //
+
+    #[rhai_fn(get = "id", pure, global)]
+    pub fn get_trade_id(trade: &mut Trade) -> String {
+        trade.id.clone()
+    }
+
+    #[rhai_fn(get = "time", pure, global)]
+    pub fn get_trade_time(trade: &mut Trade) -> i64 {
+        trade.time.timestamp_millis()
+    }
+
+    #[rhai_fn(get = "code", pure, global)]
+    pub fn get_trade_code(trade: &mut Trade) -> String {
+        trade.code.clone()
+    }
+
+    #[rhai_fn(get = "direction", pure, global)]
+    pub fn get_trade_direction(trade: &mut Trade) -> Direction {
+        trade.direction
+    }
+
+    #[rhai_fn(get = "side", pure, global)]
+    pub fn get_trade_side(trade: &mut Trade) -> Side {
+        trade.side
+    }
+
+    #[rhai_fn(get = "price", pure, global)]
+    pub fn get_trade_price(trade: &mut Trade) -> Decimal {
+        trade.price
+    }
+
+    #[rhai_fn(get = "size", pure, global)]
+    pub fn get_trade_size(trade: &mut Trade) -> Decimal {
+        trade.size
+    }
+
+    #[rhai_fn(get = "fee", pure, global)]
+    pub fn get_trade_fee(trade: &mut Trade) -> Decimal {
+        trade.fee
+    }
+
+    #[rhai_fn(get = "slippage", pure, global)]
+    pub fn get_trade_slippage(trade: &mut Trade) -> Decimal {
+        trade.slippage
+    }
+
+    #[rhai_fn(get = "rpl", pure, global)]
+    pub fn get_trade_rpl(trade: &mut Trade) -> Decimal {
+        trade.rpl
+    }
+
+    #[rhai_fn(get = "tag", pure, global)]
+    pub fn get_trade_tag(trade: &mut Trade) -> Dynamic {
+        trade.tag.clone().map_or(Dynamic::UNIT, Dynamic::from)
+    }
+
     // ================================================================ //
     // 方向持仓
     // ================================================================ //
@@ -439,6 +1481,16 @@ pub mod module {
         pos.size
     }
 
+    #[rhai_fn(get = "liq_price", pure, global)]
+    pub fn get_direction_position_liq_price(pos: &mut DirectionPosition) -> Decimal {
+        pos.liq_price
+    }
+
+    #[rhai_fn(get = "margin", pure, global)]
+    pub fn get_direction_position_margin(pos: &mut DirectionPosition) -> Decimal {
+        pos.margin
+    }
+
     // ================================================================ //
     // 持仓
     // ================================================================ //
@@ -453,6 +1505,11 @@ pub mod module {
         pos.lever
     }
 
+    #[rhai_fn(get = "margin_mode", pure, global)]
+    pub fn get_position_margin_mode(pos: &mut fuxi_quant_core::types::Position) -> MarginMode {
+        pos.margin_mode
+    }
+
     #[rhai_fn(get = "long", pure, global)]
     pub fn get_position_long(pos: &mut fuxi_quant_core::types::Position) -> DirectionPosition {
         pos.long.clone()
@@ -502,6 +1559,11 @@ pub mod module {
         s.face_val
     }
 
+    #[rhai_fn(get = "inverse", pure, global)]
+    pub fn get_symbol_inverse(s: &mut fuxi_quant_core::types::Symbol) -> bool {
+        s.inverse
+    }
+
     #[rhai_fn(get = "mark_price", pure, global)]
     pub fn get_symbol_mark_price(s: &mut fuxi_quant_core::types::Symbol) -> Decimal {
         s.mark_price
@@ -517,6 +1579,11 @@ pub mod module {
         s.funding_rate
     }
 
+    #[rhai_fn(get = "funding_interval", pure, global)]
+    pub fn get_symbol_funding_interval(s: &mut fuxi_quant_core::types::Symbol) -> i64 {
+        s.funding_interval as i64
+    }
+
     #[rhai_fn(name = "trunc_size", pure, global)]
     pub fn symbol_trunc_size(s: &mut fuxi_quant_core::types::Symbol, size: Decimal) -> Decimal {
         s.trunc_size(size)
@@ -1,5 +1,6 @@
 use anyhow::{Result, anyhow};
 use fuxi_quant_core::types::*;
+use rust_decimal::Decimal;
 use serde::{Deserialize, Serialize};
 use std::{
     path::PathBuf,
@@ -33,12 +34,22 @@ impl ScriptStrategy {
         Ok(Self { runtime })
     }
 
+    /// 脚本是否定义了指定函数(用于可选回调)
+    fn has_fn(&self, name: &str) -> bool {
+        self.runtime.ast.iter_functions().any(|f| f.name == name)
+    }
+
     fn call(&mut self, name: &str, args: impl rhai::FuncArgs) -> Result<()> {
+        self.call_with_result(name, args).map(|_| ())
+    }
+
+    fn call_with_result(&mut self, name: &str, args: impl rhai::FuncArgs) -> Result<rhai::Dynamic> {
         let gas_usage_before = self.runtime.gas_usage.load(Ordering::Relaxed);
 
-        self.runtime
+        let result = self
+            .runtime
             .engine
-            .call_fn_with_options::<()>(
+            .call_fn_with_options::<rhai::Dynamic>(
                 rhai::CallFnOptions::new().bind_this_ptr(&mut self.runtime.this),
                 &mut self.runtime.scope,
                 &self.runtime.ast,
@@ -66,7 +77,7 @@ impl ScriptStrategy {
             gas_usage_after
         );
 
-        Ok(())
+        Ok(result)
     }
 }
 
@@ -123,6 +134,37 @@ impl Strategy for ScriptStrategy {
     fn on_position(&mut self, _: &mut dyn Engine, code: &str) -> Result<()> {
         self.call("on_position", (code.to_string(),))
     }
+
+    #[tracing::instrument(skip_all)]
+    fn on_liquidation(
+        &mut self,
+        _: &mut dyn Engine,
+        code: &str,
+        direction: Direction,
+    ) -> Result<()> {
+        if !self.has_fn("on_liquidation") {
+            return Ok(());
+        }
+        self.call("on_liquidation", (code.to_string(), direction))
+    }
+
+    #[tracing::instrument(skip_all)]
+    fn on_slippage(
+        &mut self,
+        _: &mut dyn Engine,
+        order: &Order,
+        price: Decimal,
+        size: Decimal,
+    ) -> Result<Option<Decimal>> {
+        if !self.has_fn("on_slippage") {
+            return Ok(None);
+        }
+        let rate = self.call_with_result("on_slippage", (order.clone(), price, size))?;
+        let rate = rate
+            .as_decimal()
+            .map_err(|t| anyhow!("on_slippage must return decimal, got {t}"))?;
+        Ok(Some(rate))
+    }
 }
 
 pub struct Runtime {
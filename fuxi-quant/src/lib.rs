@@ -1,10 +1,14 @@
 mod log;
 pub mod types;
 
-use crate::types::Mode;
+use crate::types::{DataSource, Mode};
 use anyhow::{Result, ensure};
 use fuxi_quant_core::{backtest::Backtest, helpers::*};
 use fuxi_quant_runtime::runtime::ScriptStrategy;
+use std::sync::{
+    Arc,
+    atomic::{AtomicBool, Ordering},
+};
 use types::Config;
 
 pub fn run(config: Config) -> Result<()> {
@@ -17,8 +21,41 @@ pub fn run(config: Config) -> Result<()> {
                 ensure!(config.backtest.is_some());
                 let backtest_config = config.backtest.as_ref().unwrap();
 
-                fuxi_quant_core::backtest::history::sync_bars(
-                    &backtest_config.data_dir.to_string_lossy(),
+                let data_dir = backtest_config.data_dir.to_string_lossy();
+
+                // 币安数据源回测前同步数据, 其他数据源不访问网络
+                if matches!(backtest_config.data_source, DataSource::Binance) {
+                    // 同步进度输出到标准错误, 同一行刷新
+                    let rendered = Arc::new(AtomicBool::new(false));
+                    let rendered_flag = rendered.clone();
+                    let result = fuxi_quant_core::backtest::history::sync_bars(
+                        &data_dir,
+                        &backtest_config.codes,
+                        &backtest_config.market,
+                        time_from_str(&backtest_config.start_time)?,
+                        time_from_str(&backtest_config.end_time)?,
+                        &backtest_config.interval,
+                        backtest_config.history_bar_len,
+                        backtest_config.sync_concurrency,
+                        move |progress| {
+                            rendered_flag.store(true, Ordering::Relaxed);
+                            eprint!(
+                                "\r同步数据: {}/{} 个文件, {:.2} MB",
+                                progress.files_done,
+                                progress.files_total,
+                                progress.bytes as f64 / 1024.0 / 1024.0
+                            );
+                        },
+                    );
+                    if rendered.load(Ordering::Relaxed) {
+                        eprintln!();
+                    }
+                    result?;
+                }
+
+                let data_source = backtest_config.data_source.source(
+                    &data_dir,
+                    &backtest_config.market,
                     &backtest_config.codes,
                 )?;
 
@@ -29,12 +66,19 @@ pub fn run(config: Config) -> Result<()> {
                     &backtest_config.codes,
                     time_from_str(&backtest_config.start_time)?,
                     time_from_str(&backtest_config.end_time)?,
+                    &backtest_config.interval,
                     backtest_config.cash,
                     backtest_config.history_bar_len,
-                    backtest_config.maker_fee_rate,
-                    backtest_config.taker_fee_rate,
-                    backtest_config.slippage,
-                    &backtest_config.data_dir.to_string_lossy(),
+                    backtest_config.fee_schedule()?,
+                    backtest_config.slippage.model()?,
+                    backtest_config.participation_rate,
+                    backtest_config.liquidation_fee_rate,
+                    &backtest_config.intrabar_path,
+                    backtest_config.sub_bar,
+                    backtest_config.latency.model(),
+                    &backtest_config.position_mode,
+                    backtest_config.history_len,
+                    data_source.as_ref(),
                 )?;
 
                 let report = backtest.run()?;
@@ -50,6 +94,17 @@ pub fn run(config: Config) -> Result<()> {
                 println!("　　　盈亏比: {:.2}", report.pl_ratio);
                 println!("　　交易次数: {}", report.trade_cnt);
                 println!("　　　手续费: {:.2}", report.fee);
+                println!("　　　资金费: {:.2}", report.funding);
+                println!("　　滑点成本: {:.2}", report.slippage);
+                for (tag, tag_report) in &report.tags {
+                    println!(
+                        "　[{tag}] 盈亏: {:.2} 手续费: {:.2} 胜率: {:.2}% 交易次数: {}",
+                        tag_report.pnl,
+                        tag_report.fee,
+                        tag_report.win_rate * 100.0,
+                        tag_report.trade_cnt
+                    );
+                }
             }
             Mode::Optimize => todo!(),
             Mode::Sandbox => todo!(),
@@ -1,7 +1,15 @@
+use anyhow::{Result, anyhow};
+use fuxi_quant_core::{
+    fee::{self, FeeSchedule},
+    slippage::*,
+    source::{self, BinanceSource, ColumnMapping, FileSource},
+    symbols::SymbolStore,
+    types::{Market, SlippageModel},
+};
 use fuxi_quant_runtime::runtime::Script;
 use rust_decimal::prelude::*;
 use serde::{Deserialize, Serialize};
-use std::path::PathBuf;
+use std::{collections::HashMap, path::PathBuf};
 use tracing::level_filters::LevelFilter;
 
 /// 运行模式
@@ -73,11 +81,25 @@ pub struct Backtest {
     pub codes: Vec<String>,
     pub start_time: String,
     pub end_time: String,
+    pub interval: String,
     pub cash: Decimal,
     pub history_bar_len: usize,
     pub maker_fee_rate: Decimal,
     pub taker_fee_rate: Decimal,
-    pub slippage: Decimal,
+    pub fee_tiers: Vec<FeeTier>,
+    pub fee_overrides: HashMap<String, FeeRate>,
+    pub fee_discount: Decimal,
+    pub slippage: Slippage,
+    pub participation_rate: Decimal,
+    pub liquidation_fee_rate: Decimal,
+    pub intrabar_path: String,
+    pub sub_bar: bool,
+    pub latency: Latency,
+    pub position_mode: String,
+    pub history_len: usize,
+    pub market: String,
+    pub sync_concurrency: usize,
+    pub data_source: DataSource,
     pub data_dir: PathBuf,
 }
 
@@ -87,16 +109,212 @@ impl Default for Backtest {
             codes: Default::default(),
             start_time: Default::default(),
             end_time: Default::default(),
+            interval: "1m".into(),
             cash: dec!(1000),
             history_bar_len: 30,
             maker_fee_rate: dec!(0.0002),
             taker_fee_rate: dec!(0.0005),
-            slippage: dec!(0.0005),
+            fee_tiers: Default::default(),
+            fee_overrides: Default::default(),
+            fee_discount: dec!(0),
+            slippage: Slippage::Fixed { bps: dec!(5) },
+            participation_rate: dec!(0),
+            liquidation_fee_rate: dec!(0.005),
+            intrabar_path: "nearest".into(),
+            sub_bar: false,
+            latency: Default::default(),
+            position_mode: "hedge".into(),
+            history_len: 1000,
+            market: "um".into(),
+            sync_concurrency: 8,
+            data_source: DataSource::Binance,
             data_dir: PathBuf::from(".cache"),
         }
     }
 }
 
+/// 手续费率
+#[derive(Serialize, Deserialize, Clone, Copy)]
+pub struct FeeRate {
+    pub maker: Decimal,
+    pub taker: Decimal,
+}
+
+/// 手续费等级(VIP)
+#[derive(Serialize, Deserialize, Clone, Copy)]
+#[serde(rename_all = "kebab-case")]
+pub struct FeeTier {
+    /// 30日成交额下限
+    pub min_volume: Decimal,
+    pub maker: Decimal,
+    pub taker: Decimal,
+}
+
+impl Backtest {
+    /// 手续费方案: 基础费率为最低等级, 其余为VIP等级
+    pub fn fee_schedule(&self) -> Result<FeeSchedule> {
+        let mut schedule = FeeSchedule::new(self.maker_fee_rate, self.taker_fee_rate)?;
+        for tier in &self.fee_tiers {
+            schedule.add_tier(tier.min_volume, fee::FeeRate::new(tier.maker, tier.taker)?)?;
+        }
+        for (code, rate) in &self.fee_overrides {
+            schedule.set_override(code, fee::FeeRate::new(rate.maker, rate.taker)?);
+        }
+        schedule.set_discount(self.fee_discount)?;
+        Ok(schedule)
+    }
+}
+
+/// 延迟(毫秒)
+#[derive(Serialize, Deserialize, Default, Clone, Copy)]
+#[serde(rename_all = "kebab-case", default)]
+pub struct Latency {
+    /// 下单延迟
+    pub submit: u64,
+    /// 撤单延迟
+    pub cancel: u64,
+    /// 随机抖动上限
+    pub jitter: u64,
+    /// 随机种子
+    pub seed: u64,
+}
+
+impl Latency {
+    pub fn model(&self) -> fuxi_quant_core::backtest::Latency {
+        fuxi_quant_core::backtest::Latency::new(self.submit, self.cancel, self.jitter, self.seed)
+    }
+}
+
+/// 滑点模型
+#[derive(Serialize, Deserialize, Clone, Copy)]
+#[serde(tag = "model", rename_all = "kebab-case")]
+pub enum Slippage {
+    /// 固定滑点(基点)
+    Fixed { bps: Decimal },
+    /// 按K线振幅估算价差
+    Spread { factor: Decimal },
+    /// 按成交量参与率的平方根冲击
+    SqrtImpact { coef: Decimal },
+}
+
+impl Slippage {
+    pub fn model(&self) -> Result<Box<dyn SlippageModel>> {
+        Ok(match *self {
+            Self::Fixed { bps } => Box::new(FixedSlippage::new(bps)?),
+            Self::Spread { factor } => Box::new(SpreadSlippage::new(factor)?),
+            Self::SqrtImpact { coef } => Box::new(SqrtImpactSlippage::new(coef)?),
+        })
+    }
+}
+
+/// K线数据源
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(tag = "type", rename_all = "kebab-case")]
+pub enum DataSource {
+    /// 币安历史数据(回测前同步到数据目录)
+    Binance,
+    /// 本地 CSV / Parquet 文件
+    File {
+        /// 文件路径, {code} 替换为交易对
+        path: String,
+        /// 数据K线周期
+        interval: String,
+        #[serde(default)]
+        columns: Box<Columns>,
+        /// 交易对元数据文件(exchangeInfo 格式, JSON/YAML)
+        symbols: Option<PathBuf>,
+    },
+}
+
+impl DataSource {
+    pub fn source(
+        &self,
+        data_dir: &str,
+        market: &str,
+        codes: &[String],
+    ) -> Result<Box<dyn fuxi_quant_core::types::DataSource>> {
+        let market = Market::from_str(market)?;
+        Ok(match self {
+            Self::Binance => Box::new(BinanceSource::new(data_dir, market)?),
+            Self::File {
+                path,
+                interval,
+                columns,
+                symbols,
+            } => {
+                let mut source = FileSource::new(path, interval, columns.mapping()?)?;
+                let symbols = symbols
+                    .as_ref()
+                    .ok_or_else(|| anyhow!("文件数据源需配置交易对元数据文件 symbols"))?;
+                let symbol_store = SymbolStore::open(symbols, market)?;
+                for code in codes {
+                    source.metadata.insert_symbol(symbol_store.get(code)?);
+                }
+                Box::new(source)
+            }
+        })
+    }
+}
+
+/// 列映射
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(rename_all = "kebab-case", default)]
+pub struct Columns {
+    pub time: String,
+    /// 整数时间戳单位: s, ms, us, ns
+    pub time_unit: String,
+    /// 字符串时间格式
+    pub time_format: String,
+    pub open: String,
+    pub high: String,
+    pub low: String,
+    pub close: String,
+    pub size: String,
+    pub cash: Option<String>,
+    pub trades: Option<String>,
+    pub taker_size: Option<String>,
+    pub taker_cash: Option<String>,
+}
+
+impl Default for Columns {
+    fn default() -> Self {
+        let mapping = ColumnMapping::default();
+        Self {
+            time: mapping.time,
+            time_unit: mapping.time_unit.to_string(),
+            time_format: mapping.time_format,
+            open: mapping.open,
+            high: mapping.high,
+            low: mapping.low,
+            close: mapping.close,
+            size: mapping.size,
+            cash: mapping.cash,
+            trades: mapping.trades,
+            taker_size: mapping.taker_size,
+            taker_cash: mapping.taker_cash,
+        }
+    }
+}
+
+impl Columns {
+    pub fn mapping(&self) -> Result<ColumnMapping> {
+        Ok(ColumnMapping {
+            time: self.time.clone(),
+            time_unit: source::EpochUnit::from_str(&self.time_unit)?,
+            time_format: self.time_format.clone(),
+            open: self.open.clone(),
+            high: self.high.clone(),
+            low: self.low.clone(),
+            close: self.close.clone(),
+            size: self.size.clone(),
+            cash: self.cash.clone(),
+            trades: self.trades.clone(),
+            taker_size: self.taker_size.clone(),
+            taker_cash: self.taker_cash.clone(),
+        })
+    }
+}
+
 #[derive(Serialize, Deserialize, Default)]
 pub struct Optimize {}
 